@@ -1,24 +1,95 @@
 use anyhow::Result;
-use chrono::Utc;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
+use chrono::{Duration, Utc};
+use futures::{pin_mut, Stream, TryStreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use tracing::{debug, info, warn};
 
-use crate::bluesky::{Embed, Image, Post};
-use crate::database::{ArchivedImage, ArchivedPost, Database};
+use crate::bluesky::{DownloadFailed, External, Image, Post, Repost, ThreadView, Video};
+use crate::database::{
+    ArchivedCaption, ArchivedExternal, ArchivedImage, ArchivedPost, ArchivedRepost, ArchivedVideo,
+    Database, FailedBlob, ThreadLink,
+};
+use crate::paginator::{CursorCallback, FeedPage};
+use crate::retry::RetryPolicy;
+
+/// How long a resolved PDS endpoint is trusted before the DID document is fetched again.
+const PDS_CACHE_HOURS: i64 = 24;
+
+/// Largest file downloaded from a link card. GIF picker files are a few MB at
+/// most; links can point anywhere, so anything bigger is left alone.
+const MAX_LINKED_MEDIA_BYTES: u64 = 50 * 1024 * 1024;
 
 pub struct Archiver<'a> {
     db: Database,
     output_dir: PathBuf,
     client: &'a crate::bluesky::Client,
+    archive_externals: bool,
+    progress: Option<MultiProgress>,
+    source: Option<&'static str>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ArchiveStats {
     pub downloaded: usize,
     pub skipped: usize,
     pub failed: usize,
+    /// Failed posts that a later run may archive, e.g. after a timeout or a
+    /// 503; the rest failed for good, such as blobs deleted from their PDS
+    pub retryable: usize,
+}
+
+impl ArchiveStats {
+    /// Adds the counts of another run, e.g. when archiving several sources.
+    pub fn merge(&mut self, other: ArchiveStats) {
+        self.downloaded += other.downloaded;
+        self.skipped += other.skipped;
+        self.failed += other.failed;
+        self.retryable += other.retryable;
+    }
+}
+
+/// Files of a single post downloaded, skipped and failed.
+#[derive(Debug, Default)]
+struct MediaCounts {
+    downloaded: usize,
+    skipped: usize,
+    failed: usize,
+    /// Failures a retry could fix; see [`ArchiveStats::retryable`]
+    retryable: usize,
+}
+
+/// An item of a paged source that [`Archiver::archive_pages`] can archive.
+pub trait ArchiveItem {
+    fn post(&self) -> &Post;
+
+    /// Records what the source knows about the post beyond the post itself.
+    /// Only called for posts that are archived.
+    fn save_details(&self, _db: &Database) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ArchiveItem for Post {
+    fn post(&self) -> &Post {
+        self
+    }
+}
+
+impl ArchiveItem for Repost {
+    fn post(&self) -> &Post {
+        &self.post
+    }
+
+    fn save_details(&self, db: &Database) -> Result<()> {
+        db.save_repost(&ArchivedRepost {
+            post_uri: self.post.uri.clone(),
+            reposted_by_did: self.by.did.clone(),
+            reposted_by_handle: self.by.handle.clone(),
+            reposted_at: self.reposted_at.clone(),
+        })
+    }
 }
 
 impl<'a> Archiver<'a> {
@@ -27,14 +98,36 @@ impl<'a> Archiver<'a> {
             db,
             output_dir,
             client,
+            archive_externals: false,
+            progress: None,
+            source: None,
         }
     }
 
+    /// Also archive link cards: their thumbnails, the linked file when the
+    /// link points at a GIF or other media, and the card's URI, title and
+    /// description.
+    pub fn set_archive_externals(&mut self, enabled: bool) {
+        self.archive_externals = enabled;
+    }
+
+    /// Record that archived posts were found through `source` (e.g. "timeline").
+    pub fn set_source(&mut self, source: &'static str) {
+        self.source = Some(source);
+    }
+
+    /// Draw progress bars in `progress`, so sources archived concurrently
+    /// each get their own line instead of overwriting one another.
+    pub fn set_progress(&mut self, progress: MultiProgress) {
+        self.progress = Some(progress);
+    }
+
     pub async fn archive_posts(&self, posts: Vec<Post>, nsfw_only: bool) -> Result<ArchiveStats> {
         let mut stats = ArchiveStats {
             downloaded: 0,
             skipped: 0,
             failed: 0,
+            retryable: 0,
         };
 
         // Filter posts based on nsfw_only flag
@@ -74,22 +167,84 @@ impl<'a> Archiver<'a> {
 
         // Process posts sequentially (database isn't thread-safe)
         for post in posts_to_process.iter() {
-            let is_nsfw = post.has_nsfw_labels();
-            pb.set_message(format!("Processing @{}", post.author.handle));
-
-            match self.archive_post(post, is_nsfw).await {
-                Ok((downloaded, skipped)) => {
-                    stats.downloaded += downloaded;
-                    stats.skipped += skipped;
-                    pb.inc((downloaded + skipped) as u64);
+            self.process_post(post, &mut stats, &pb).await;
+        }
+
+        pb.finish_with_message(format!(
+            "Complete! Downloaded: {}, Skipped: {}, Failed: {}",
+            stats.downloaded, stats.skipped, stats.failed
+        ));
+
+        Ok(stats)
+    }
+
+    /// Archives posts page by page as they are fetched.
+    ///
+    /// Each page is downloaded before the next one is requested, so downloads
+    /// start with the first page and only one page is held in memory at a time.
+    ///
+    /// `checkpoint` is called with a page's cursor once every post on it is on
+    /// disk and in the database. After the first failure that a retry could
+    /// fix no further checkpoints are made, so resuming from the last one
+    /// retries the failed posts instead of skipping them. Failures that no
+    /// retry will fix are recorded in the database instead, so resuming moves
+    /// past them.
+    pub async fn archive_pages<S, T>(
+        &self,
+        pages: S,
+        nsfw_only: bool,
+        checkpoint: Option<CursorCallback>,
+    ) -> Result<ArchiveStats>
+    where
+        S: Stream<Item = Result<FeedPage<T>>>,
+        T: ArchiveItem,
+    {
+        let mut stats = ArchiveStats {
+            downloaded: 0,
+            skipped: 0,
+            failed: 0,
+            retryable: 0,
+        };
+
+        // The total isn't known up front, so count images as they are processed
+        let pb = match &self.progress {
+            Some(progress) => progress.add(ProgressBar::new_spinner()),
+            None => ProgressBar::new_spinner(),
+        };
+        pb.set_style(
+            ProgressStyle::default_spinner().template(
+                "{spinner:.green} [{elapsed_precise}] {pos} images ({per_sec}) | {msg}",
+            )?,
+        );
+        pb.enable_steady_tick(std::time::Duration::from_millis(100));
+
+        let mut posts_seen = 0;
+        pin_mut!(pages);
+        while let Some(page) = pages.try_next().await.inspect_err(|_| pb.abandon())? {
+            let retryable_before = stats.retryable;
+            for item in page.items.iter() {
+                let post = item.post();
+                if nsfw_only && !post.has_nsfw_labels() {
+                    continue;
                 }
-                Err(e) => {
-                    warn!("Failed to archive post {}: {}", post.uri, e);
-                    stats.failed += 1;
+                posts_seen += 1;
+                self.process_post(item, &mut stats, &pb).await;
+            }
+
+            if stats.retryable > retryable_before {
+                warn!("Some posts on this page failed; resume checkpoint not advanced");
+            }
+            if let (Some(cursor), Some(checkpoint)) = (&page.cursor, &checkpoint) {
+                if stats.retryable == 0 {
+                    checkpoint(cursor);
                 }
             }
         }
 
+        if posts_seen == 0 {
+            info!("No posts to process after filtering");
+        }
+
         pb.finish_with_message(format!(
             "Complete! Downloaded: {}, Skipped: {}, Failed: {}",
             stats.downloaded, stats.skipped, stats.failed
@@ -98,7 +253,98 @@ impl<'a> Archiver<'a> {
         Ok(stats)
     }
 
-    async fn archive_post(&self, post: &Post, is_nsfw: bool) -> Result<(usize, usize)> {
+    /// Archives the media of every post in a thread, and records each post's
+    /// parent and root so the thread can be rebuilt later.
+    pub async fn archive_thread(
+        &self,
+        thread: ThreadView,
+        nsfw_only: bool,
+    ) -> Result<ArchiveStats> {
+        let posts = thread.into_posts();
+        info!("Thread has {} posts", posts.len());
+
+        for post in &posts {
+            let (parent_uri, root_uri) = match &post.record.reply {
+                Some(reply) => (Some(reply.parent.uri.clone()), reply.root.uri.clone()),
+                None => (None, post.uri.clone()),
+            };
+            self.db.save_thread_link(&ThreadLink {
+                post_uri: post.uri.clone(),
+                parent_uri,
+                root_uri,
+            })?;
+        }
+
+        self.archive_posts(posts, nsfw_only).await
+    }
+
+    async fn process_post<T: ArchiveItem>(
+        &self,
+        item: &T,
+        stats: &mut ArchiveStats,
+        pb: &ProgressBar,
+    ) {
+        let post = item.post();
+        let is_nsfw = post.has_nsfw_labels();
+        pb.set_message(format!("Processing @{}", post.author.handle));
+
+        let result = self.archive_post(post, is_nsfw).await;
+        if result.is_ok() {
+            if let Err(e) = self.save_details(item) {
+                warn!("Failed to record details of {}: {}", post.uri, e);
+            }
+        }
+        Self::tally(result, post, stats, pb);
+
+        if let Some(quoted) = post.quoted_post() {
+            let result = self.archive_quote(post, &quoted).await;
+            Self::tally(result, &quoted, stats, pb);
+        }
+    }
+
+    fn tally(result: Result<MediaCounts>, post: &Post, stats: &mut ArchiveStats, pb: &ProgressBar) {
+        match result {
+            Ok(counts) => {
+                stats.downloaded += counts.downloaded;
+                stats.skipped += counts.skipped;
+                if counts.failed > 0 {
+                    stats.failed += 1;
+                }
+                if counts.retryable > 0 {
+                    stats.retryable += 1;
+                }
+                pb.inc((counts.downloaded + counts.skipped + counts.failed) as u64);
+            }
+            Err(e) => {
+                warn!("Failed to archive post {}: {}", post.uri, e);
+                stats.failed += 1;
+                if is_retryable(&e) {
+                    stats.retryable += 1;
+                }
+            }
+        }
+    }
+
+    /// Archives the media of a quoted post and records the quote, saving the
+    /// quoting post too so the context is kept even if it has no media itself.
+    async fn archive_quote(&self, post: &Post, quoted: &Post) -> Result<MediaCounts> {
+        if !self.has_media(quoted) {
+            return Ok(MediaCounts::default());
+        }
+
+        debug!("Archiving media of {} quoted by {}", quoted.uri, post.uri);
+        let counts = self.archive_post(quoted, quoted.has_nsfw_labels()).await?;
+
+        if !self.db.is_post_archived(&post.uri)? {
+            self.save_post_metadata(post, 0, post.has_nsfw_labels())?;
+        }
+        self.db.save_quote(&post.uri, &quoted.uri)?;
+
+        Ok(counts)
+    }
+
+    /// Returns the number of files downloaded, skipped and failed.
+    async fn archive_post(&self, post: &Post, is_nsfw: bool) -> Result<MediaCounts> {
         // Check if we've already processed this post
         if self.db.is_post_archived(&post.uri)? {
             debug!(
@@ -108,33 +354,15 @@ impl<'a> Archiver<'a> {
         }
 
         let images = self.extract_images(post);
-        if images.is_empty() {
-            debug!("No images found in post {}", post.uri);
-            return Ok((0, 0));
+        let video = self.extract_video(post);
+        let external = self.extract_external(post);
+        if images.is_empty() && video.is_none() && external.is_none() {
+            debug!("No images or video found in post {}", post.uri);
+            return Ok(MediaCounts::default());
         }
 
         // Save post metadata
-        let archived_post = ArchivedPost {
-            uri: post.uri.clone(),
-            cid: post.cid.clone(),
-            author_did: post.author.did.clone(),
-            author_handle: post.author.handle.clone(),
-            post_text: post
-                .record
-                .get("text")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string()),
-            image_count: images.len() as i32,
-            archived_at: Utc::now(),
-            post_created_at: post
-                .record
-                .get("createdAt")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string(),
-            has_content_warning: is_nsfw,
-        };
-        self.db.save_post(&archived_post)?;
+        self.save_post_metadata(post, images.len(), is_nsfw)?;
 
         // Create author directory, with NSFW subdirectory if needed
         let base_dir = if is_nsfw {
@@ -145,8 +373,7 @@ impl<'a> Archiver<'a> {
         let author_dir = base_dir.join(&post.author.handle);
         fs::create_dir_all(&author_dir).await?;
 
-        let mut downloaded = 0;
-        let mut skipped = 0;
+        let mut counts = MediaCounts::default();
 
         // Download each image
         for (idx, image) in images.iter().enumerate() {
@@ -155,34 +382,14 @@ impl<'a> Archiver<'a> {
             // Check if already downloaded
             if self.db.is_image_archived(blob_cid)? {
                 debug!("Image {} already downloaded", blob_cid);
-                skipped += 1;
+                counts.skipped += 1;
                 continue;
             }
 
             // Generate filename
-            let extension = match image.image.mime_type.as_str() {
-                "image/jpeg" => "jpg",
-                "image/png" => "png",
-                "image/gif" => "gif",
-                "image/webp" => "webp",
-                _ => "bin",
-            };
+            let extension = image_extension(&image.image.mime_type);
 
-            let timestamp = post
-                .record
-                .get("createdAt")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .replace(":", "-")
-                .replace(".", "-");
-            let filename = format!(
-                "{}_{}_{}_{}.{}",
-                post.author.handle,
-                timestamp,
-                &post.cid[..8],
-                idx,
-                extension
-            );
+            let filename = format!("{}_{}.{}", file_prefix(post), idx, extension);
             let file_path = author_dir.join(&filename);
 
             // Download image
@@ -205,43 +412,362 @@ impl<'a> Archiver<'a> {
                     self.db.save_image(&archived_image)?;
 
                     info!("Downloaded: {}", filename);
-                    downloaded += 1;
+                    counts.downloaded += 1;
                 }
+                Err(e) => self.download_failed(post, blob_cid, "image", e, &mut counts),
+            }
+        }
+
+        if let Some(video) = video {
+            match self.archive_video(post, video, &author_dir).await {
+                Ok(true) => counts.downloaded += 1,
+                Ok(false) => counts.skipped += 1,
                 Err(e) => {
-                    warn!("Failed to download image {}: {}", blob_cid, e);
+                    self.download_failed(post, &video.video.ref_.link, "video", e, &mut counts)
                 }
             }
         }
 
-        Ok((downloaded, skipped))
+        if let Some(external) = external {
+            if self.db.is_external_archived(&post.uri)? {
+                debug!("Link card of {} already archived", post.uri);
+                counts.skipped += 1;
+            } else {
+                self.archive_external(post, external, &author_dir, &mut counts)
+                    .await?;
+            }
+        }
+
+        Ok(counts)
     }
 
-    fn extract_images(&self, post: &Post) -> Vec<Image> {
-        if let Some(embed_value) = post.record.get("embed") {
-            debug!("Found embed in post: {:?}", embed_value);
-            if let Ok(Embed::Images {
-                images: img_list, ..
-            }) = serde_json::from_value::<Embed>(embed_value.clone())
+    /// Downloads a link card's thumbnail and, if the link points at a GIF or
+    /// other media file, the file itself, adding them to `counts`.
+    ///
+    /// The card is only recorded once nothing is left that a retry could fix,
+    /// so those failures are retried on the next run.
+    async fn archive_external(
+        &self,
+        post: &Post,
+        external: &External,
+        author_dir: &Path,
+        counts: &mut MediaCounts,
+    ) -> Result<()> {
+        let prefix = file_prefix(post);
+        let retryable_before = counts.retryable;
+
+        let mut thumb_filename = None;
+        if let Some(thumb) = &external.thumb {
+            let extension = image_extension(&thumb.mime_type);
+            let filename = format!("{}_thumb.{}", prefix, extension);
+            match self
+                .download_blob(&post.author.did, &thumb.ref_.link, "thumbnail")
+                .await
             {
-                debug!("Successfully parsed {} images from embed", img_list.len());
-                return img_list;
-            } else {
-                debug!("Failed to parse embed as Images type");
+                Ok(bytes) => {
+                    fs::write(author_dir.join(&filename), bytes).await?;
+                    info!("Downloaded: {}", filename);
+                    thumb_filename = Some(filename);
+                    counts.downloaded += 1;
+                }
+                Err(e) => self.download_failed(post, &thumb.ref_.link, "thumbnail", e, counts),
+            }
+        }
+
+        let mut media_filename = None;
+        if let Some(extension) = linked_media_extension(&external.uri) {
+            let filename = format!("{}_link.{}", prefix, extension);
+            let path = author_dir.join(&filename);
+            match self
+                .client
+                .download_linked_media(&external.uri, &path, MAX_LINKED_MEDIA_BYTES)
+                .await
+            {
+                Ok(_) => {
+                    info!("Downloaded: {}", filename);
+                    media_filename = Some(filename);
+                    counts.downloaded += 1;
+                }
+                Err(e) => self.download_failed(post, &external.uri, "linked media", e, counts),
+            }
+        }
+
+        if counts.retryable == retryable_before {
+            self.db.save_external(&ArchivedExternal {
+                post_uri: post.uri.clone(),
+                uri: external.uri.clone(),
+                title: external.title.clone(),
+                description: external.description.clone(),
+                thumb_cid: external.thumb.as_ref().map(|t| t.ref_.link.clone()),
+                thumb_filename,
+                media_filename,
+                archived_at: Utc::now(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Logs a failed download and counts it. Failures a retry could fix are
+    /// left for the next run; the rest, such as a blob deleted from its PDS,
+    /// are recorded in the database so resuming moves past them.
+    fn download_failed(
+        &self,
+        post: &Post,
+        blob: &str,
+        what: &str,
+        error: anyhow::Error,
+        counts: &mut MediaCounts,
+    ) {
+        counts.failed += 1;
+        if is_retryable(&error) {
+            warn!("Failed to download {} {}: {}", what, blob, error);
+            counts.retryable += 1;
+            return;
+        }
+
+        warn!(
+            "Failed to download {} {} of {}, not retrying: {}",
+            what, blob, post.uri, error
+        );
+        let failed = FailedBlob {
+            post_uri: post.uri.clone(),
+            blob: blob.to_string(),
+            what: what.to_string(),
+            error: error.to_string(),
+            failed_at: Utc::now(),
+        };
+        if let Err(e) = self.db.save_failed_blob(&failed) {
+            warn!("Failed to record the failed download of {}: {}", blob, e);
+        }
+    }
+
+    /// Downloads a post's video and its captions. Returns false if the video
+    /// was already archived.
+    ///
+    /// The original upload is fetched with getBlob; if that fails, the
+    /// transcoded HLS stream is assembled from the video service instead.
+    async fn archive_video(&self, post: &Post, video: &Video, author_dir: &Path) -> Result<bool> {
+        let did = &post.author.did;
+        let blob_cid = &video.video.ref_.link;
+
+        if self.db.is_video_archived(blob_cid)? {
+            debug!("Video {} already downloaded", blob_cid);
+            return Ok(false);
+        }
+
+        let playlist_url = self.client.get_video_playlist_url(did, blob_cid);
+        let (bytes, mime_type, source, duration) =
+            match self.download_blob(did, blob_cid, "video").await {
+                Ok(bytes) => {
+                    // The duration is only known from the playlist; it's nice to have
+                    let duration = match self.client.hls_duration(&playlist_url).await {
+                        Ok(duration) => Some(duration),
+                        Err(e) => {
+                            debug!("Couldn't read duration of video {}: {}", blob_cid, e);
+                            None
+                        }
+                    };
+                    (bytes, video.video.mime_type.clone(), "blob", duration)
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to download video blob {}, falling back to HLS: {}",
+                        blob_cid, e
+                    );
+                    let (bytes, playlist) = self.client.download_hls(&playlist_url).await?;
+                    let mime_type = playlist.mime_type().to_string();
+                    (bytes, mime_type, "hls", Some(playlist.duration))
+                }
+            };
+
+        let extension = match mime_type.as_str() {
+            "video/mp4" => "mp4",
+            "video/webm" => "webm",
+            "video/quicktime" => "mov",
+            "video/mp2t" => "ts",
+            _ => "bin",
+        };
+        let prefix = file_prefix(post);
+        let filename = format!("{}_video.{}", prefix, extension);
+        fs::write(author_dir.join(&filename), &bytes).await?;
+
+        let archived_video = ArchivedVideo {
+            id: 0, // auto-increment
+            post_uri: post.uri.clone(),
+            blob_cid: blob_cid.clone(),
+            filename: filename.clone(),
+            mime_type,
+            size: bytes.len() as i64,
+            duration,
+            aspect_width: video.aspect_ratio.as_ref().map(|a| a.width),
+            aspect_height: video.aspect_ratio.as_ref().map(|a| a.height),
+            alt_text: video.alt.clone().filter(|s| !s.is_empty()),
+            source: source.to_string(),
+            downloaded_at: Utc::now(),
+        };
+        self.db.save_video(&archived_video)?;
+        info!("Downloaded: {}", filename);
+
+        for caption in &video.captions {
+            let caption_cid = &caption.file.ref_.link;
+            let caption_filename = format!("{}_video.{}.vtt", prefix, caption.lang);
+            match self.download_blob(did, caption_cid, "captions").await {
+                Ok(bytes) => {
+                    fs::write(author_dir.join(&caption_filename), bytes).await?;
+                    self.db.save_caption(&ArchivedCaption {
+                        video_cid: blob_cid.clone(),
+                        lang: caption.lang.clone(),
+                        blob_cid: caption_cid.clone(),
+                        filename: caption_filename,
+                    })?;
+                }
+                Err(e) => warn!(
+                    "Failed to download {} captions for video {}: {}",
+                    caption.lang, blob_cid, e
+                ),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Records where a post was found and what its source knows about it.
+    fn save_details<T: ArchiveItem>(&self, item: &T) -> Result<()> {
+        let post = item.post();
+        // Posts without media aren't archived, so there's nothing to attach them to.
+        // A skipped post may have been archived by an earlier run, hence the lookup.
+        if !self.db.is_post_archived(&post.uri)? {
+            return Ok(());
+        }
+        if let Some(source) = self.source {
+            self.db.save_post_source(&post.uri, source)?;
+        }
+        item.save_details(&self.db)
+    }
+
+    fn save_post_metadata(&self, post: &Post, image_count: usize, is_nsfw: bool) -> Result<()> {
+        let archived_post = ArchivedPost {
+            uri: post.uri.clone(),
+            cid: post.cid.clone(),
+            author_did: post.author.did.clone(),
+            author_handle: post.author.handle.clone(),
+            post_text: post.record.text.clone(),
+            image_count: image_count as i32,
+            archived_at: Utc::now(),
+            post_created_at: post.record.created_at.clone(),
+            has_content_warning: is_nsfw,
+        };
+        self.db.save_post(&archived_post)
+    }
+
+    fn extract_images<'p>(&self, post: &'p Post) -> &'p [Image] {
+        match &post.record.embed {
+            Some(embed) => embed.images(),
+            None => {
+                debug!("No embed found in post record");
+                &[]
             }
-        } else {
-            debug!("No embed found in post record");
         }
+    }
 
-        Vec::new()
+    fn extract_external<'p>(&self, post: &'p Post) -> Option<&'p External> {
+        if !self.archive_externals {
+            return None;
+        }
+        post.record.embed.as_ref()?.external()
+    }
+
+    fn has_media(&self, post: &Post) -> bool {
+        !self.extract_images(post).is_empty()
+            || self.extract_video(post).is_some()
+            || self.extract_external(post).is_some()
+    }
+
+    fn extract_video<'p>(&self, post: &'p Post) -> Option<&'p Video> {
+        post.record.embed.as_ref()?.video()
     }
 
     async fn download_image(&self, did: &str, blob_cid: &str, path: &PathBuf) -> Result<u64> {
-        let url = self.client.get_image_url(did, blob_cid);
-        let bytes = self.client.download_image(&url).await?;
+        let bytes = self.download_blob(did, blob_cid, "image").await?;
         let size = bytes.len() as u64;
 
         fs::write(path, bytes).await?;
 
         Ok(size)
     }
+
+    /// Fetches a blob from its author's PDS, or the default blob host if the
+    /// PDS can't be resolved.
+    async fn download_blob(&self, did: &str, blob_cid: &str, what: &str) -> Result<Vec<u8>> {
+        let url = match self.resolve_pds(did).await {
+            Ok(pds) => self.client.get_blob_url(&pds, did, blob_cid),
+            Err(e) => {
+                warn!(
+                    "Failed to resolve PDS for {}, using default blob host: {}",
+                    did, e
+                );
+                self.client.get_image_url(did, blob_cid)
+            }
+        };
+        self.client.download(&url, what).await
+    }
+
+    async fn resolve_pds(&self, did: &str) -> Result<String> {
+        if let Some((pds, resolved_at)) = self.db.get_pds_endpoint(did)? {
+            if Utc::now() - resolved_at < Duration::hours(PDS_CACHE_HOURS) {
+                return Ok(pds);
+            }
+        }
+
+        let pds = self.client.resolve_pds(did).await?;
+        debug!("Resolved PDS for {}: {}", did, pds);
+        self.db.save_pds_endpoint(did, &pds)?;
+        Ok(pds)
+    }
+}
+
+/// Whether a later run could succeed where `error` failed, by the same rules
+/// [`RetryPolicy`] uses to retry a request.
+fn is_retryable(error: &anyhow::Error) -> bool {
+    let status = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<DownloadFailed>());
+    match status {
+        Some(failed) => RetryPolicy::is_retryable_status(failed.status),
+        None => RetryPolicy::is_retryable_error(error),
+    }
+}
+
+/// Common start of the filenames for a post's media: handle, creation time
+/// and a short CID.
+fn file_prefix(post: &Post) -> String {
+    let timestamp = post.record.created_at.replace(":", "-").replace(".", "-");
+    format!("{}_{}_{}", post.author.handle, timestamp, &post.cid[..8])
+}
+
+fn image_extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => "bin",
+    }
+}
+
+/// File extension of a link that points straight at a GIF or other media
+/// file, such as the Tenor links the GIF picker embeds.
+fn linked_media_extension(uri: &str) -> Option<&'static str> {
+    let url = reqwest::Url::parse(uri).ok()?;
+    let extension = url.path().rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "gif" => Some("gif"),
+        "mp4" => Some("mp4"),
+        "webm" => Some("webm"),
+        "webp" => Some("webp"),
+        "png" => Some("png"),
+        "jpg" | "jpeg" => Some("jpg"),
+        _ => None,
+    }
 }
@@ -0,0 +1,88 @@
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use std::fmt;
+
+pub const FEED_GENERATOR: &str = "app.bsky.feed.generator";
+pub const LIST: &str = "app.bsky.graph.list";
+pub const POST: &str = "app.bsky.feed.post";
+
+/// An `at://` URI naming a single record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtUri {
+    /// DID or handle of the repo holding the record
+    pub authority: String,
+    pub collection: String,
+    pub rkey: String,
+}
+
+impl AtUri {
+    /// Parses an `at://authority/collection/rkey` URI, or the bsky.app web URL
+    /// of a post, feed or list (`https://bsky.app/profile/<actor>/feed/<rkey>`).
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        let invalid = || anyhow!("Not an at:// URI or bsky.app URL: {}", input);
+
+        if let Some(path) = input.strip_prefix("at://") {
+            let mut parts = path.trim_end_matches('/').split('/');
+            return match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(authority), Some(collection), Some(rkey), None)
+                    if !authority.is_empty() && !collection.is_empty() && !rkey.is_empty() =>
+                {
+                    Ok(Self {
+                        authority: authority.to_string(),
+                        collection: collection.to_string(),
+                        rkey: rkey.to_string(),
+                    })
+                }
+                _ => Err(invalid()),
+            };
+        }
+
+        let url = Url::parse(input).map_err(|_| invalid())?;
+        let segments: Vec<_> = url
+            .path_segments()
+            .ok_or_else(invalid)?
+            .filter(|s| !s.is_empty())
+            .collect();
+        let collection = match segments.as_slice() {
+            ["profile", _, "feed", _] => FEED_GENERATOR,
+            ["profile", _, "lists", _] => LIST,
+            ["profile", _, "post", _] => POST,
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self {
+            authority: segments[1].to_string(),
+            collection: collection.to_string(),
+            rkey: segments[3].to_string(),
+        })
+    }
+
+    /// Fails unless the URI names a record in `collection`.
+    pub fn expect(self, collection: &str) -> Result<Self> {
+        if self.collection != collection {
+            return Err(anyhow!("Expected a {} URI, got {}", collection, self));
+        }
+        Ok(self)
+    }
+
+    /// Whether the authority is already a DID rather than a handle.
+    pub fn has_did(&self) -> bool {
+        self.authority.starts_with("did:")
+    }
+
+    /// Short name safe to use in a filename, e.g. for cursor files.
+    pub fn file_key(&self) -> String {
+        format!("{}_{}", self.authority, self.rkey).replace([':', '/', '.'], "_")
+    }
+}
+
+impl fmt::Display for AtUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at://{}/{}/{}",
+            self.authority, self.collection, self.rkey
+        )
+    }
+}
@@ -1,150 +1,187 @@
 use anyhow::{anyhow, Result};
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client as HttpClient;
+use futures::{Stream, TryStreamExt};
+use reqwest::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use reqwest::{Client as HttpClient, Request, RequestBuilder, Response, StatusCode};
 use serde::Deserialize;
 use serde_json::json;
-use std::time::Instant;
-use tokio::time::{sleep, Duration};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
 use tracing::{info, warn};
 
-const API_BASE: &str = "https://bsky.social/xrpc";
+use crate::aturi::{self, AtUri};
+use crate::hls::{self, MediaPlaylist};
+use crate::identity::{IdentityResolver, DEFAULT_PLC_DIRECTORY};
+use crate::lexicon::lenient;
+use crate::oauth::{self, DpopKey};
+use crate::paginator::{into_items, FeedPage, ListRequest, Page, PaginateOptions};
+use crate::ratelimit::RateLimits;
+use crate::retry::RetryPolicy;
+use crate::search::SearchQuery;
+use crate::session::{Credentials, Session, SessionRejected, SessionStore, StoredSession};
 
-type CursorCallback = Box<dyn Fn(&str) + Send>;
+/// Default host used for every XRPC service when no override is configured.
+pub const DEFAULT_SERVICE_URL: &str = "https://bsky.social";
+
+/// AppView that serves public data without authentication.
+pub const PUBLIC_APPVIEW_URL: &str = "https://public.api.bsky.app";
+
+/// Service that transcodes uploaded videos and serves them as HLS.
+pub const VIDEO_SERVICE_URL: &str = "https://video.bsky.app";
+
+/// Most posts app.bsky.feed.getPosts returns per request.
+const GET_POSTS_BATCH_SIZE: usize = 25;
+
+pub use crate::lexicon::{
+    AspectRatio, Author, BlobRef, Caption, Embed, EmbedView, External, FeedReason, Image, Label,
+    Post, ReasonRepost, Record, ReplyRef, StrongRef, ThreadView, ThreadViewPost, Video, View,
+};
+pub use crate::paginator::CursorCallback;
 
 #[derive(Debug, Clone)]
 pub struct Client {
     http: HttpClient,
-    session: Option<Session>,
+    endpoints: Endpoints,
+    identity: IdentityResolver,
+    // Shared between clones so a refresh made by one request is seen by all
+    session: Arc<RwLock<Option<StoredSession>>>,
+    refresh_lock: Arc<Mutex<()>>,
+    session_store: Option<SessionStore>,
+    retry: RetryPolicy,
+    limits: Arc<RateLimits>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)]
-struct Session {
-    did: String,
-    #[serde(rename = "accessJwt")]
-    access_jwt: String,
+/// Base URLs of the services the client talks to.
+///
+/// The XRPC services default to `bsky.social`, which proxies app.bsky.* reads
+/// to the AppView, but they can be pointed at a self-hosted PDS, the public
+/// AppView or a local mock server independently.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    /// AppView used for feed reads (getActorLikes, getAuthorFeed)
+    pub appview: String,
+    /// PDS used for account calls (createSession)
+    pub pds: String,
+    /// Host serving com.atproto.sync.getBlob when an author's PDS can't be resolved
+    pub blob_host: String,
+    /// PLC directory used to resolve did:plc identities
+    pub plc_directory: String,
+    /// Video service serving HLS playlists for uploaded videos
+    pub video: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct LoginResponse {
-    did: String,
-    #[serde(rename = "accessJwt")]
-    access_jwt: String,
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            appview: DEFAULT_SERVICE_URL.to_string(),
+            pds: DEFAULT_SERVICE_URL.to_string(),
+            blob_host: DEFAULT_SERVICE_URL.to_string(),
+            plc_directory: DEFAULT_PLC_DIRECTORY.to_string(),
+            video: VIDEO_SERVICE_URL.to_string(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct Post {
-    pub uri: String,
-    pub cid: String,
-    pub author: Author,
-    pub record: serde_json::Value,
-    #[serde(rename = "indexedAt")]
-    pub indexed_at: String,
-    pub labels: Option<Vec<Label>>,
+fn xrpc_url(base: &str, nsid: &str) -> String {
+    format!("{}/xrpc/{}", base.trim_end_matches('/'), nsid)
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct Label {
-    pub src: String,
-    pub uri: String,
-    pub val: String,
-    #[serde(rename = "cts")]
-    pub created_at: String,
+struct XrpcError {
+    error: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct Author {
-    pub did: String,
-    pub handle: String,
-    #[serde(rename = "displayName")]
-    pub display_name: Option<String>,
+struct ResolveHandleResponse {
+    did: String,
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct Record {
-    #[serde(rename = "$type")]
-    pub record_type: String,
-    pub text: Option<String>,
-    pub embed: Option<Embed>,
-    #[serde(rename = "createdAt")]
-    pub created_at: String,
+/// A post found on an author feed because the author reposted it.
+#[derive(Debug, Clone)]
+pub struct Repost {
+    pub post: Post,
+    /// Account that reposted the post
+    pub by: Author,
+    /// When the repost was made, as indexed by the AppView
+    pub reposted_at: String,
 }
 
+/// Returned by login when the account has email 2FA enabled and needs the emailed code.
+#[derive(Debug)]
+pub struct AuthFactorTokenRequired;
+
+impl std::fmt::Display for AuthFactorTokenRequired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Login failed: this account requires the sign-in code sent to its email (AuthFactorTokenRequired)"
+        )
+    }
+}
+
+impl std::error::Error for AuthFactorTokenRequired {}
+
+/// Returned by [`Client::download`] when the server answers with an error
+/// status, so callers can tell a deleted blob from a transient failure.
+#[derive(Debug)]
+pub struct DownloadFailed {
+    pub what: String,
+    pub status: StatusCode,
+}
+
+impl std::fmt::Display for DownloadFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to download {}: {}", self.what, self.status)
+    }
+}
+
+impl std::error::Error for DownloadFailed {}
+
+/// Response of getActorLikes, getFeed, getListFeed and getTimeline.
 #[derive(Debug, Deserialize)]
-#[serde(untagged)]
-#[allow(dead_code)]
-pub enum Embed {
-    Images {
-        #[serde(rename = "$type")]
-        embed_type: String,
-        images: Vec<Image>,
-    },
-    External {
-        #[serde(rename = "$type")]
-        embed_type: String,
-        external: External,
-    },
-    Other(serde_json::Value),
+struct GetFeedResponse {
+    pub feed: Vec<FeedItem>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct Image {
-    pub alt: Option<String>,
-    pub fullsize: Option<String>,
-    pub thumb: Option<String>,
-    #[serde(rename = "aspectRatio")]
-    pub aspect_ratio: Option<AspectRatio>,
-    pub image: View,
+struct FeedItem {
+    pub post: Post,
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct AspectRatio {
-    pub width: u32,
-    pub height: u32,
+struct GetPostsResponse {
+    pub posts: Vec<Post>,
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct View {
-    #[serde(rename = "$type")]
-    pub type_: String,
-    #[serde(rename = "ref")]
-    pub ref_: BlobRef,
-    #[serde(rename = "mimeType")]
-    pub mime_type: String,
-    pub size: u64,
+struct GetPostThreadResponse {
+    pub thread: ThreadView,
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct BlobRef {
-    #[serde(rename = "$link")]
-    pub link: String,
+struct SearchPostsResponse {
+    pub posts: Vec<Post>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct External {
-    pub uri: String,
-    pub title: String,
-    pub description: String,
+struct GetFollowsResponse {
+    pub follows: Vec<Author>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-struct GetLikesResponse {
-    pub feed: Vec<FeedItem>,
+struct GetListResponse {
+    pub items: Vec<ListItem>,
     pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-struct FeedItem {
-    pub post: Post,
+struct ListItem {
+    pub subject: Author,
 }
 
 #[derive(Debug, Deserialize)]
@@ -156,36 +193,74 @@ struct GetAuthorFeedResponse {
 #[derive(Debug, Deserialize)]
 struct AuthorFeedItem {
     pub post: Post,
-    pub reason: Option<serde_json::Value>, // Used to identify reposts
+    /// Set on reposts and pinned posts
+    #[serde(default, deserialize_with = "lenient")]
+    pub reason: Option<FeedReason>,
 }
 
-impl Post {
-    pub fn has_nsfw_labels(&self) -> bool {
-        if let Some(labels) = &self.labels {
-            labels.iter().any(|label| {
-                matches!(
-                    label.val.as_str(),
-                    "porn"
-                        | "sexual"
-                        | "nudity"
-                        | "graphic-media"
-                        | "self-harm"
-                        | "sensitive"
-                        | "content-warning"
-                )
-            })
-        } else {
-            false
-        }
+impl Page for GetFeedResponse {
+    type Item = FeedItem;
+
+    fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    fn into_items(self) -> Vec<FeedItem> {
+        self.feed
+    }
+}
+
+impl Page for SearchPostsResponse {
+    type Item = Post;
+
+    fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    fn into_items(self) -> Vec<Post> {
+        self.posts
+    }
+}
+
+impl Page for GetFollowsResponse {
+    type Item = Author;
+
+    fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    fn into_items(self) -> Vec<Author> {
+        self.follows
+    }
+}
+
+impl Page for GetListResponse {
+    type Item = ListItem;
+
+    fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    fn into_items(self) -> Vec<ListItem> {
+        self.items
+    }
+}
+
+impl Page for GetAuthorFeedResponse {
+    type Item = AuthorFeedItem;
+
+    fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    fn into_items(self) -> Vec<AuthorFeedItem> {
+        self.feed
     }
 }
 
 impl Default for Client {
     fn default() -> Self {
-        Self {
-            http: HttpClient::new(),
-            session: None,
-        }
+        Self::with_endpoints(Endpoints::default())
     }
 }
 
@@ -194,198 +269,478 @@ impl Client {
         Self::default()
     }
 
+    /// Client for anonymous use: reads go to the public AppView and nothing
+    /// requires logging in, as long as only public data is requested.
+    pub fn public() -> Self {
+        Self::with_endpoints(Endpoints {
+            appview: PUBLIC_APPVIEW_URL.to_string(),
+            ..Default::default()
+        })
+    }
+
+    pub fn with_endpoints(endpoints: Endpoints) -> Self {
+        let http = HttpClient::new();
+        let identity = IdentityResolver::new(http.clone(), &endpoints.plc_directory);
+        Self {
+            http,
+            endpoints,
+            identity,
+            session: Arc::new(RwLock::new(None)),
+            refresh_lock: Arc::new(Mutex::new(())),
+            session_store: None,
+            retry: RetryPolicy::default(),
+            limits: Arc::new(RateLimits::default()),
+        }
+    }
+
+    /// Replaces the policy used to retry listing calls and blob downloads.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// Replaces the rate limit budgets shared by this client and its clones.
+    pub fn set_rate_limits(&mut self, limits: RateLimits) {
+        self.limits = Arc::new(limits);
+    }
+
+    pub fn rate_limits(&self) -> &RateLimits {
+        &self.limits
+    }
+
+    /// Saves the session to `store` whenever it changes, so later runs can reuse it.
+    pub fn set_session_store(&mut self, store: SessionStore) {
+        self.session_store = Some(store);
+    }
+
+    /// Reuses the stored app password session for `identifier` if it is
+    /// still usable, otherwise falls back to a fresh login.
+    pub async fn resume_or_login(
+        &mut self,
+        identifier: &str,
+        password: &str,
+        auth_factor_token: Option<&str>,
+    ) -> Result<()> {
+        if self.resume_saved_session(identifier, false).await? {
+            return Ok(());
+        }
+
+        self.login_with_auth_factor(identifier, password, auth_factor_token)
+            .await
+    }
+
+    /// Reuses the stored OAuth session for `identifier` if it is still
+    /// usable, otherwise runs the interactive OAuth flow.
+    pub async fn resume_or_login_oauth<F>(&mut self, identifier: &str, open_url: F) -> Result<()>
+    where
+        F: FnOnce(&str),
+    {
+        if self.resume_saved_session(identifier, true).await? {
+            return Ok(());
+        }
+
+        self.login_oauth(identifier, open_url).await
+    }
+
+    /// Loads the saved session and checks it still works. Returns `false`
+    /// when there's no usable session and a fresh login is needed; network
+    /// and server errors are returned instead, so a flaky run doesn't burn
+    /// a login.
+    async fn resume_saved_session(&self, identifier: &str, oauth: bool) -> Result<bool> {
+        let stored = match self.session_store.as_ref().map(|store| store.load()) {
+            Some(Ok(Some(stored))) => stored,
+            Some(Err(e)) => {
+                warn!("Failed to load saved session: {}", e);
+                return Ok(false);
+            }
+            _ => return Ok(false),
+        };
+
+        let is_oauth = matches!(stored.credentials, Credentials::OAuth(_));
+        if stored.identifier != identifier
+            || stored.service != self.endpoints.pds
+            || is_oauth != oauth
+        {
+            return Ok(false);
+        }
+
+        *self.session.write().unwrap() = Some(stored);
+
+        // getSession goes through the usual refresh-on-expiry path
+        match self.check_session().await {
+            Ok(()) => {
+                info!("Reusing saved session for {}", identifier);
+                Ok(true)
+            }
+            Err(e) if e.is::<SessionRejected>() => {
+                warn!("Saved session is no longer valid, logging in again: {}", e);
+                *self.session.write().unwrap() = None;
+                Ok(false)
+            }
+            Err(e) => Err(e.context("Failed to check the saved session")),
+        }
+    }
+
+    async fn check_session(&self) -> Result<()> {
+        let url = xrpc_url(
+            &self.authed_service(&self.endpoints.pds),
+            "com.atproto.server.getSession",
+        );
+        let response = self
+            .retry
+            .send("Checking saved session", || {
+                self.send_authed(|http| http.get(&url))
+            })
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            let message = format!("{} - {}", status, error_text);
+            // send_authed only hands these back once refreshing can't help
+            if status == StatusCode::BAD_REQUEST || status == StatusCode::UNAUTHORIZED {
+                return Err(SessionRejected(message).into());
+            }
+            return Err(anyhow!(message));
+        }
+
+        Ok(())
+    }
+
     pub async fn login(&mut self, identifier: &str, password: &str) -> Result<()> {
-        let url = format!("{}/com.atproto.server.createSession", API_BASE);
+        self.login_with_auth_factor(identifier, password, None)
+            .await
+    }
 
-        let body = json!({
+    /// Logs in, passing the sign-in code emailed to accounts with 2FA enabled.
+    ///
+    /// Fails with [`AuthFactorTokenRequired`] when the account needs a code
+    /// and none (or an empty one) was given.
+    pub async fn login_with_auth_factor(
+        &mut self,
+        identifier: &str,
+        password: &str,
+        auth_factor_token: Option<&str>,
+    ) -> Result<()> {
+        let url = xrpc_url(&self.endpoints.pds, "com.atproto.server.createSession");
+
+        let mut body = json!({
             "identifier": identifier,
             "password": password
         });
+        if let Some(token) = auth_factor_token.filter(|t| !t.is_empty()) {
+            body["authFactorToken"] = json!(token.trim());
+        }
 
         let response = self.http.post(&url).json(&body).send().await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await?;
+            let error = serde_json::from_str::<XrpcError>(&error_text)
+                .ok()
+                .and_then(|e| e.error);
+            if error.as_deref() == Some("AuthFactorTokenRequired") {
+                return Err(AuthFactorTokenRequired.into());
+            }
             return Err(anyhow!("Login failed: {}", error_text));
         }
 
-        let login_response: LoginResponse = response.json().await?;
+        let session: Session = response.json().await?;
 
-        self.session = Some(Session {
-            did: login_response.did.clone(),
-            access_jwt: login_response.access_jwt,
-        });
+        info!("Successfully logged in as DID: {}", session.did);
+        self.set_credentials(identifier, Credentials::AppPassword(session));
+        Ok(())
+    }
 
-        info!("Successfully logged in as DID: {}", login_response.did);
+    /// Logs in with atproto OAuth instead of an app password.
+    ///
+    /// `open_url` receives the authorization URL the user has to open in a
+    /// browser; the flow finishes once the browser is redirected back.
+    pub async fn login_oauth<F>(&mut self, identifier: &str, open_url: F) -> Result<()>
+    where
+        F: FnOnce(&str),
+    {
+        let did = self.resolve_handle(identifier).await?;
+        let pds = self.resolve_pds(&did).await?;
+        let session = oauth::authorize(&self.http, &did, &pds, identifier, open_url).await?;
+
+        self.set_credentials(identifier, Credentials::OAuth(session));
         Ok(())
     }
 
-    pub async fn get_likes_with_options(
-        &self,
-        actor: &str,
-        limit: usize,
-        delay_ms: u64,
-        start_cursor: Option<String>,
-        cursor_callback: Option<CursorCallback>,
-    ) -> Result<Vec<Post>> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or_else(|| anyhow!("Not authenticated"))?;
+    /// Resolves a handle to its DID; DIDs are returned unchanged.
+    pub async fn resolve_handle(&self, handle: &str) -> Result<String> {
+        let handle = handle.trim_start_matches('@');
+        if handle.starts_with("did:") {
+            return Ok(handle.to_string());
+        }
 
-        let mut all_posts = Vec::new();
-        let mut cursor: Option<String> = start_cursor;
-        // Use larger page size for better performance, API supports up to 100
-        let page_size = if limit == 0 { 100 } else { 100.min(limit) };
+        let url = xrpc_url(
+            &self.endpoints.appview,
+            "com.atproto.identity.resolveHandle",
+        );
+        let response = self
+            .http
+            .get(&url)
+            .query(&[("handle", handle)])
+            .send()
+            .await?;
 
-        if cursor.is_some() {
-            info!("Resuming from saved cursor position");
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(anyhow!(
+                "Failed to resolve handle {}: {} - {}",
+                handle,
+                status,
+                error_text
+            ));
         }
 
-        // Create progress bar
-        let pb = if limit == 0 {
-            ProgressBar::new_spinner()
-        } else {
-            ProgressBar::new(limit as u64)
-        };
+        let resolved: ResolveHandleResponse = response.json().await?;
+        Ok(resolved.did)
+    }
+
+    /// Refreshes the access token using whichever method the session came from.
+    pub async fn refresh_session(&self) -> Result<()> {
+        let credentials = self.credentials()?;
 
-        pb.set_style(
-            if limit == 0 {
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} [{elapsed_precise}] {pos} posts fetched ({per_sec}) {msg}")?
-            } else {
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} posts ({per_sec}) {msg}")?
-                    .progress_chars("=>-")
+        let refreshed = match credentials {
+            Credentials::AppPassword(session) => {
+                Credentials::AppPassword(self.refresh_app_password_session(&session).await?)
             }
-        );
+            Credentials::OAuth(session) => {
+                Credentials::OAuth(oauth::refresh(&self.http, &session).await?)
+            }
+        };
 
-        if limit == 0 {
-            pb.set_message("Fetching all liked posts...");
+        info!("Refreshed session for DID: {}", refreshed.did());
+        if let Some(stored) = self.session.write().unwrap().as_mut() {
+            stored.credentials = refreshed;
         }
+        self.persist_session();
+        Ok(())
+    }
 
-        let mut retry_count = 0;
-        let max_retries = 5;
+    async fn refresh_app_password_session(&self, session: &Session) -> Result<Session> {
+        let url = xrpc_url(&self.endpoints.pds, "com.atproto.server.refreshSession");
 
-        loop {
-            let url = format!("{}/app.bsky.feed.getActorLikes", API_BASE);
-
-            let mut params = vec![
-                ("actor", actor.to_string()),
-                ("limit", page_size.to_string()),
-            ];
+        let response = self
+            .http
+            .post(&url)
+            .bearer_auth(&session.refresh_jwt)
+            .send()
+            .await?;
 
-            if let Some(ref c) = cursor {
-                params.push(("cursor", c.clone()));
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            let message = format!("Failed to refresh session: {} - {}", status, error_text);
+            if status.is_client_error() {
+                return Err(SessionRejected(message).into());
             }
+            return Err(anyhow!(message));
+        }
 
-            // Add delay if specified
-            if delay_ms > 0 && !all_posts.is_empty() {
-                sleep(Duration::from_millis(delay_ms)).await;
-            }
+        Ok(response.json().await?)
+    }
 
-            let _request_start = Instant::now();
-            let response = self
-                .http
-                .get(&url)
-                .bearer_auth(&session.access_jwt)
-                .query(&params)
-                .send()
-                .await?;
+    fn set_credentials(&self, identifier: &str, credentials: Credentials) {
+        *self.session.write().unwrap() = Some(StoredSession {
+            identifier: identifier.to_string(),
+            service: self.endpoints.pds.clone(),
+            credentials,
+        });
+        self.persist_session();
+    }
 
-            let status = response.status();
+    fn persist_session(&self) {
+        let (Some(store), Some(stored)) =
+            (&self.session_store, self.session.read().unwrap().clone())
+        else {
+            return;
+        };
 
-            // Handle rate limiting
-            if status.as_u16() == 429 {
-                retry_count += 1;
-                if retry_count > max_retries {
-                    pb.finish_and_clear();
-                    return Err(anyhow!(
-                        "Rate limited after {} retries. Try again later or use --delay flag",
-                        max_retries
-                    ));
-                }
+        if let Err(e) = store.save(&stored) {
+            warn!(
+                "Failed to save session to {}: {}",
+                store.path().display(),
+                e
+            );
+        }
+    }
 
-                let wait_time = 2u64.pow(retry_count) * 1000; // Exponential backoff in ms
-                pb.set_message(format!(
-                    "Rate limited! Waiting {}s before retry {}/{}...",
-                    wait_time / 1000,
-                    retry_count,
-                    max_retries
-                ));
-                sleep(Duration::from_millis(wait_time)).await;
-                continue;
-            }
+    fn credentials(&self) -> Result<Credentials> {
+        self.session
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|stored| stored.credentials.clone())
+            .ok_or_else(|| anyhow!("Not authenticated"))
+    }
 
-            if !status.is_success() {
-                let error_text = response.text().await?;
-                pb.finish_and_clear();
-                return Err(anyhow!(
-                    "Failed to fetch likes: {} - {}",
-                    status,
-                    error_text
-                ));
-            }
+    pub fn is_authenticated(&self) -> bool {
+        self.credentials().is_ok()
+    }
 
-            // Reset retry count on success
-            retry_count = 0;
-
-            let response_text = response.text().await?;
-            let likes_response: GetLikesResponse = match serde_json::from_str(&response_text) {
-                Ok(resp) => resp,
-                Err(e) => {
-                    // Log the error and response for debugging
-                    warn!("Failed to parse likes response: {}", e);
-                    warn!("Response text: {}", response_text);
-                    pb.finish_and_clear();
-                    return Err(anyhow!("Failed to parse likes response: {}", e));
-                }
-            };
+    fn access_token(&self) -> Result<String> {
+        self.credentials()
+            .map(|credentials| credentials.access_token().to_string())
+    }
 
-            let new_posts = likes_response.feed.len();
+    /// OAuth tokens are only accepted by the account's own PDS, which proxies
+    /// app.bsky.* calls to the AppView, so route authenticated calls there.
+    fn authed_service(&self, default: &str) -> String {
+        match self.credentials() {
+            Ok(Credentials::OAuth(session)) => session.pds,
+            _ => default.to_string(),
+        }
+    }
 
-            // Check if we got any posts
-            if new_posts == 0 {
-                info!("No more posts returned, reached end of likes");
-                break;
+    /// Attaches the Authorization header (and a DPoP proof for OAuth sessions).
+    fn authorize(&self, request: &mut Request, credentials: &Credentials) -> Result<()> {
+        let (authorization, proof) = match credentials {
+            Credentials::AppPassword(session) => (format!("Bearer {}", session.access_jwt), None),
+            Credentials::OAuth(session) => {
+                let key = DpopKey::from_encoded(&session.dpop_key)?;
+                let proof = key.proof(
+                    request.method().as_str(),
+                    request.url().as_str(),
+                    session.resource_nonce.as_deref(),
+                    Some(&session.access_token),
+                )?;
+                (format!("DPoP {}", session.access_token), Some(proof))
             }
+        };
 
-            for item in likes_response.feed {
-                all_posts.push(item.post);
+        let headers = request.headers_mut();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(&authorization)?);
+        if let Some(proof) = proof {
+            headers.insert("DPoP", HeaderValue::from_str(&proof)?);
+        }
+        Ok(())
+    }
 
-                if limit > 0 {
-                    pb.inc(1);
-                    if all_posts.len() >= limit {
-                        pb.finish_with_message("Fetching complete");
-                        return Ok(all_posts);
-                    }
+    fn update_resource_nonce(&self, nonce: &str) {
+        if let Some(StoredSession {
+            credentials: Credentials::OAuth(session),
+            ..
+        }) = self.session.write().unwrap().as_mut()
+        {
+            session.resource_nonce = Some(nonce.to_string());
+        }
+    }
+
+    /// Sends a request with the session's credentials when logged in, or
+    /// anonymously otherwise, for endpoints that serve public data.
+    ///
+    /// Transient failures are retried under the client's retry policy.
+    pub(crate) async fn send_maybe_authed<F>(&self, description: &str, build: F) -> Result<Response>
+    where
+        F: Fn(&HttpClient) -> RequestBuilder,
+    {
+        self.retry
+            .send(description, || async {
+                if self.credentials().is_ok() {
+                    self.send_authed(&build).await
+                } else {
+                    self.execute(build(&self.http).build()?).await
                 }
+            })
+            .await
+    }
+
+    /// Sends a request once its rate limit budget allows, and updates the
+    /// budget from the response headers.
+    ///
+    /// The body is read here rather than by the caller, so a connection that
+    /// drops while the body streams in fails inside the retry loop and the
+    /// request is sent again.
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let limiter = self.limits.for_url(request.url().as_str());
+        if let Some(limiter) = &limiter {
+            limiter.acquire().await;
+        }
+        let response = self.http.execute(request).await?;
+        if let Some(limiter) = &limiter {
+            limiter.update(response.headers());
+        }
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+        let mut buffered = http::Response::new(body);
+        *buffered.status_mut() = status;
+        *buffered.headers_mut() = headers;
+        Ok(Response::from(buffered))
+    }
+
+    /// Sends a request authorized with the current session.
+    ///
+    /// If the server rejects the token as expired, the session is refreshed
+    /// and the same request is built and sent again, so callers keep their
+    /// cursor and other state across the refresh. OAuth requests are also
+    /// retried once when the PDS asks for a new DPoP nonce.
+    async fn send_authed<F>(&self, build: F) -> Result<Response>
+    where
+        F: Fn(&HttpClient) -> RequestBuilder,
+    {
+        let mut refreshed = false;
+        let mut renewed_nonce = false;
+
+        loop {
+            let credentials = self.credentials()?;
+            let mut request = build(&self.http).build()?;
+            self.authorize(&mut request, &credentials)?;
+            let response = self.execute(request).await?;
+
+            if let Some(nonce) = response
+                .headers()
+                .get("dpop-nonce")
+                .and_then(|v| v.to_str().ok())
+            {
+                self.update_resource_nonce(nonce);
             }
 
-            if limit == 0 {
-                pb.set_message(format!("Fetched {} posts...", all_posts.len()));
-                pb.inc(new_posts as u64);
+            let status = response.status();
+            if status != StatusCode::BAD_REQUEST && status != StatusCode::UNAUTHORIZED {
+                return Ok(response);
             }
 
-            cursor = likes_response.cursor;
+            // The error code is in the body, so read it and hand an equivalent
+            // response back to the caller when there's nothing to retry
+            let headers = response.headers().clone();
+            let body = response.bytes().await?;
+            let www_authenticate = headers.get(WWW_AUTHENTICATE).and_then(|v| v.to_str().ok());
+            let expired = serde_json::from_slice::<XrpcError>(&body)
+                .map(|e| e.error.as_deref() == Some("ExpiredToken"))
+                .unwrap_or(false)
+                || www_authenticate.is_some_and(|h| h.contains("invalid_token"));
+
+            if !renewed_nonce && oauth::is_use_dpop_nonce(status, www_authenticate, &body) {
+                renewed_nonce = true;
+                continue;
+            }
 
-            if cursor.is_none() {
-                info!("No cursor returned, reached end of likes");
-                break;
+            if !expired || refreshed {
+                let mut rebuilt = http::Response::new(body);
+                *rebuilt.status_mut() = status;
+                *rebuilt.headers_mut() = headers;
+                return Ok(Response::from(rebuilt));
             }
 
-            // Save cursor position if callback provided
-            if let (Some(ref c), Some(ref callback)) = (&cursor, &cursor_callback) {
-                callback(c);
+            {
+                let _guard = self.refresh_lock.lock().await;
+                // Another request may have refreshed while we waited for the lock
+                if self.access_token()? == credentials.access_token() {
+                    info!("Access token expired, refreshing session");
+                    self.refresh_session().await?;
+                }
             }
+            refreshed = true;
         }
-
-        pb.finish_with_message(format!("Fetched all {} posts", all_posts.len()));
-        info!("Total posts fetched: {}", all_posts.len());
-        Ok(all_posts)
     }
 
-    pub async fn get_user_posts_with_options(
+    pub async fn get_likes_with_options(
         &self,
         actor: &str,
         limit: usize,
@@ -393,212 +748,569 @@ impl Client {
         start_cursor: Option<String>,
         cursor_callback: Option<CursorCallback>,
     ) -> Result<Vec<Post>> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or_else(|| anyhow!("Not authenticated"))?;
+        let options = PaginateOptions {
+            limit,
+            delay_ms,
+            start_cursor,
+            cursor_callback,
+            ..Default::default()
+        };
+        let posts: Vec<Post> = self.stream_likes(actor, options).try_collect().await?;
 
-        let mut all_posts = Vec::new();
-        let mut cursor: Option<String> = start_cursor;
-        let page_size = if limit == 0 { 100 } else { 100.min(limit) };
+        info!("Total posts fetched: {}", posts.len());
+        Ok(posts)
+    }
 
-        if cursor.is_some() {
-            info!("Resuming from saved cursor position");
-        }
+    /// Streams the posts `actor` has liked. Requires authentication.
+    pub fn stream_likes<'a>(
+        &'a self,
+        actor: &str,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<Post>> + 'a {
+        into_items(self.likes_pages(actor, options))
+    }
 
-        // Create progress bar
-        let pb = if limit == 0 {
-            ProgressBar::new_spinner()
-        } else {
-            ProgressBar::new(limit as u64)
+    /// Pages through the posts `actor` has liked, one page per item.
+    pub fn likes_pages<'a>(
+        &'a self,
+        actor: &str,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<FeedPage<Post>>> + 'a {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.feed.getActorLikes",
+            ),
+            params: vec![("actor", actor.to_string())],
+            description: "likes",
+            require_auth: true,
         };
 
-        pb.set_style(
-            if limit == 0 {
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} [{elapsed_precise}] {pos} posts fetched ({per_sec}) {msg}")?
-            } else {
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} posts ({per_sec}) {msg}")?
-                    .progress_chars("=>-")
-            }
-        );
+        self.paginate::<GetFeedResponse, _, _>(request, options, |item: FeedItem| Some(item.post))
+    }
 
-        if limit == 0 {
-            pb.set_message("Fetching all user posts...");
-        }
+    /// Pages through the posts of a custom feed, given as an at:// URI of the
+    /// feed generator with a DID authority (see [`Client::resolve_at_uri`]).
+    pub fn feed_pages<'a>(
+        &'a self,
+        feed: &AtUri,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<FeedPage<Post>>> + 'a {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.feed.getFeed",
+            ),
+            params: vec![("feed", feed.to_string())],
+            description: "feed posts",
+            require_auth: false,
+        };
 
-        let mut retry_count = 0;
-        let max_retries = 5;
+        self.paginate::<GetFeedResponse, _, _>(request, options, |item: FeedItem| Some(item.post))
+    }
 
-        loop {
-            let url = format!("{}/app.bsky.feed.getAuthorFeed", API_BASE);
+    /// Pages through the posts by members of a list, newest first, given as
+    /// an at:// URI of the list with a DID authority.
+    pub fn list_feed_pages<'a>(
+        &'a self,
+        list: &AtUri,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<FeedPage<Post>>> + 'a {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.feed.getListFeed",
+            ),
+            params: vec![("list", list.to_string())],
+            description: "list feed posts",
+            require_auth: false,
+        };
 
-            let mut params = vec![
-                ("actor", actor.to_string()),
-                ("limit", page_size.to_string()),
-                ("filter", "posts_with_media".to_string()), // Only get posts with media
-            ];
+        self.paginate::<GetFeedResponse, _, _>(request, options, |item: FeedItem| Some(item.post))
+    }
 
-            if let Some(ref c) = cursor {
-                params.push(("cursor", c.clone()));
-            }
+    /// Pages through the posts matching a search query.
+    pub fn search_pages<'a>(
+        &'a self,
+        query: &SearchQuery,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<FeedPage<Post>>> + 'a {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.feed.searchPosts",
+            ),
+            params: query.params(),
+            description: "search results",
+            require_auth: false,
+        };
 
-            // Add delay if specified
-            if delay_ms > 0 && !all_posts.is_empty() {
-                sleep(Duration::from_millis(delay_ms)).await;
-            }
+        self.paginate::<SearchPostsResponse, _, _>(request, options, Some)
+    }
 
-            let response = self
-                .http
-                .get(&url)
-                .bearer_auth(&session.access_jwt)
-                .query(&params)
-                .send()
-                .await?;
+    /// Fetches every member of a list.
+    pub async fn list_members(&self, list: &AtUri, delay_ms: u64) -> Result<Vec<Author>> {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.graph.getList",
+            ),
+            params: vec![("list", list.to_string())],
+            description: "list members",
+            require_auth: false,
+        };
+        let options = PaginateOptions {
+            delay_ms,
+            progress: false,
+            ..Default::default()
+        };
 
-            let status = response.status();
+        let pages = self.paginate::<GetListResponse, _, _>(request, options, |item: ListItem| {
+            Some(item.subject)
+        });
+        into_items(pages).try_collect().await
+    }
 
-            // Handle rate limiting
-            if status.as_u16() == 429 {
-                retry_count += 1;
-                if retry_count > max_retries {
-                    pb.finish_and_clear();
-                    return Err(anyhow!(
-                        "Rate limited after {} retries. Try again later or use --delay flag",
-                        max_retries
-                    ));
-                }
+    /// Fetches every account `actor` follows.
+    pub async fn follows(&self, actor: &str, delay_ms: u64) -> Result<Vec<Author>> {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.graph.getFollows",
+            ),
+            params: vec![("actor", actor.to_string())],
+            description: "follows",
+            require_auth: false,
+        };
+        let options = PaginateOptions {
+            delay_ms,
+            progress: false,
+            ..Default::default()
+        };
 
-                let wait_time = 2u64.pow(retry_count) * 1000; // Exponential backoff in ms
-                pb.set_message(format!(
-                    "Rate limited! Waiting {}s before retry {}/{}...",
-                    wait_time / 1000,
-                    retry_count,
-                    max_retries
-                ));
-                sleep(Duration::from_millis(wait_time)).await;
-                continue;
-            }
+        let pages = self.paginate::<GetFollowsResponse, _, _>(request, options, Some);
+        into_items(pages).try_collect().await
+    }
 
+    /// Pages through the logged-in account's home timeline, reposts included.
+    /// Requires authentication.
+    pub fn timeline_pages(
+        &self,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<FeedPage<Post>>> + '_ {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.feed.getTimeline",
+            ),
+            params: Vec::new(),
+            description: "timeline",
+            require_auth: true,
+        };
+
+        self.paginate::<GetFeedResponse, _, _>(request, options, |item: FeedItem| Some(item.post))
+    }
+
+    /// Fetches posts by URI, in batches of the most getPosts accepts.
+    /// Deleted posts and posts the viewer can't see are left out.
+    pub async fn get_posts(&self, posts: &[AtUri]) -> Result<Vec<Post>> {
+        let url = xrpc_url(
+            &self.authed_service(&self.endpoints.appview),
+            "app.bsky.feed.getPosts",
+        );
+        let mut fetched = Vec::with_capacity(posts.len());
+
+        for batch in posts.chunks(GET_POSTS_BATCH_SIZE) {
+            let params: Vec<_> = batch.iter().map(|uri| ("uris", uri.to_string())).collect();
+            let response = self
+                .send_maybe_authed("Fetching posts", |http| http.get(&url).query(&params))
+                .await?;
+
+            let status = response.status();
             if !status.is_success() {
                 let error_text = response.text().await?;
-                pb.finish_and_clear();
                 return Err(anyhow!(
-                    "Failed to fetch user posts: {} - {}",
+                    "Failed to fetch posts: {} - {}",
                     status,
                     error_text
                 ));
             }
 
-            // Reset retry count on success
-            retry_count = 0;
-
-            let response_text = response.text().await?;
-            let feed_response: GetAuthorFeedResponse = match serde_json::from_str(&response_text) {
-                Ok(resp) => resp,
-                Err(e) => {
-                    warn!("Failed to parse feed response: {}", e);
-                    warn!("Response text: {}", response_text);
-                    pb.finish_and_clear();
-                    return Err(anyhow!("Failed to parse feed response: {}", e));
-                }
-            };
+            let found = response.json::<GetPostsResponse>().await?.posts;
+            if found.len() < batch.len() {
+                warn!(
+                    "{} of {} posts not found (deleted or not visible)",
+                    batch.len() - found.len(),
+                    batch.len()
+                );
+            }
+            fetched.extend(found);
+        }
 
-            let mut new_posts_count = 0;
-            let feed_items = feed_response.feed;
-            let feed_empty = feed_items.is_empty();
+        Ok(fetched)
+    }
 
-            // Filter out reposts and quote posts, only keep original posts with images
-            for item in feed_items {
-                // Skip if it's a repost (has reason field)
-                if item.reason.is_some() {
-                    continue;
-                }
+    /// Fetches the thread around a post: up to `parent_height` posts above it
+    /// and `depth` levels of replies below it.
+    pub async fn get_post_thread(
+        &self,
+        post: &AtUri,
+        depth: u32,
+        parent_height: u32,
+    ) -> Result<ThreadView> {
+        let url = xrpc_url(
+            &self.authed_service(&self.endpoints.appview),
+            "app.bsky.feed.getPostThread",
+        );
+        let params = [
+            ("uri", post.to_string()),
+            ("depth", depth.to_string()),
+            ("parentHeight", parent_height.to_string()),
+        ];
+        let response = self
+            .send_maybe_authed("Fetching thread", |http| http.get(&url).query(&params))
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(anyhow!(
+                "Failed to fetch thread of {}: {} - {}",
+                post,
+                status,
+                error_text
+            ));
+        }
 
-                let post = item.post;
+        let thread = response.json::<GetPostThreadResponse>().await?.thread;
+        match thread {
+            ThreadView::Post(_) => Ok(thread),
+            ThreadView::Blocked { .. } => Err(anyhow!("Post {} is blocked", post)),
+            _ => Err(anyhow!("Post {} not found", post)),
+        }
+    }
 
-                // Skip quote posts (check if embed type is record)
-                if let Some(embed_value) = post.record.get("embed") {
-                    if let Some(embed_type) = embed_value.get("$type").and_then(|v| v.as_str()) {
-                        if embed_type.contains("record") {
-                            continue; // Skip quote posts
-                        }
-                    }
-                }
+    pub async fn get_user_posts_with_options(
+        &self,
+        actor: &str,
+        limit: usize,
+        delay_ms: u64,
+        start_cursor: Option<String>,
+        cursor_callback: Option<CursorCallback>,
+    ) -> Result<Vec<Post>> {
+        let options = PaginateOptions {
+            limit,
+            delay_ms,
+            start_cursor,
+            cursor_callback,
+            ..Default::default()
+        };
+        self.stream_user_posts(actor, options).try_collect().await
+    }
 
-                // Only include posts with image embeds
-                if let Some(embed_value) = post.record.get("embed") {
-                    if let Ok(Embed::Images { .. }) =
-                        serde_json::from_value::<Embed>(embed_value.clone())
-                    {
-                        all_posts.push(post);
-                        new_posts_count += 1;
-
-                        if limit > 0 {
-                            pb.inc(1);
-                            if all_posts.len() >= limit {
-                                pb.finish_with_message("Fetching complete");
-                                return Ok(all_posts);
-                            }
-                        }
-                    }
-                }
-            }
+    /// Streams `actor`'s own image posts, skipping reposts and quote posts.
+    pub fn stream_user_posts<'a>(
+        &'a self,
+        actor: &str,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<Post>> + 'a {
+        into_items(self.user_posts_pages(actor, options))
+    }
 
-            if limit == 0 {
-                pb.set_message(format!("Fetched {} image posts...", all_posts.len()));
-                pb.inc(new_posts_count);
-            }
+    /// Pages through `actor`'s own image posts, skipping reposts and quote posts.
+    pub fn user_posts_pages<'a>(
+        &'a self,
+        actor: &str,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<FeedPage<Post>>> + 'a {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.feed.getAuthorFeed",
+            ),
+            params: vec![
+                ("actor", actor.to_string()),
+                ("filter", "posts_with_media".to_string()), // Only get posts with media
+            ],
+            description: "user posts",
+            require_auth: false,
+        };
 
-            // Check if we got any posts at all
-            if feed_empty {
-                info!("No more posts returned, reached end of feed");
-                break;
+        self.paginate::<GetAuthorFeedResponse, _, _>(request, options, |item: AuthorFeedItem| {
+            // Skip if it's a repost (has reason field)
+            if item.reason.is_some() {
+                return None;
             }
 
-            cursor = feed_response.cursor;
+            // Only include posts with images or video of their own. Plain quote posts
+            // are skipped, but quotes with attached media (recordWithMedia) are kept.
+            let has_media = item.post.record.embed.as_ref()?.has_media();
+            has_media.then_some(item.post)
+        })
+    }
 
-            if cursor.is_none() {
-                info!("No cursor returned, reached end of feed");
-                break;
-            }
+    /// Pages through the image posts `actor` has reposted, newest repost first.
+    /// Their own posts and pinned posts are skipped.
+    pub fn reposts_pages<'a>(
+        &'a self,
+        actor: &str,
+        options: PaginateOptions,
+    ) -> impl Stream<Item = Result<FeedPage<Repost>>> + 'a {
+        let request = ListRequest {
+            url: xrpc_url(
+                &self.authed_service(&self.endpoints.appview),
+                "app.bsky.feed.getAuthorFeed",
+            ),
+            // posts_with_media leaves reposts out, so filter on the client instead
+            params: vec![
+                ("actor", actor.to_string()),
+                ("filter", "posts_no_replies".to_string()),
+            ],
+            description: "reposts",
+            require_auth: false,
+        };
+
+        self.paginate::<GetAuthorFeedResponse, _, _>(request, options, |item: AuthorFeedItem| {
+            let FeedReason::Repost(reason) = item.reason? else {
+                return None;
+            };
+
+            // As with the user's own posts, only count reposts with media to archive
+            let has_media = item.post.record.embed.as_ref()?.has_media();
+            has_media.then_some(Repost {
+                post: item.post,
+                by: reason.by,
+                reposted_at: reason.indexed_at,
+            })
+        })
+    }
+
+    /// Parses a feed at:// URI or bsky.app feed URL, resolving a handle in it to a DID.
+    pub async fn resolve_feed_uri(&self, feed: &str) -> Result<AtUri> {
+        let uri = AtUri::parse(feed)?.expect(aturi::FEED_GENERATOR)?;
+        self.resolve_at_uri(uri).await
+    }
 
-            // Save cursor position if callback provided
-            if let (Some(ref c), Some(ref callback)) = (&cursor, &cursor_callback) {
-                callback(c);
+    /// Parses a list at:// URI or bsky.app list URL, resolving a handle in it to a DID.
+    pub async fn resolve_list_uri(&self, list: &str) -> Result<AtUri> {
+        let uri = AtUri::parse(list)?.expect(aturi::LIST)?;
+        self.resolve_at_uri(uri).await
+    }
+
+    /// Parses a post at:// URI or bsky.app post URL, resolving a handle in it to a DID.
+    pub async fn resolve_post_uri(&self, post: &str) -> Result<AtUri> {
+        let uri = AtUri::parse(post)?.expect(aturi::POST)?;
+        self.resolve_at_uri(uri).await
+    }
+
+    /// Parses a batch of post at:// URIs or bsky.app post URLs, resolving
+    /// each handle in them once.
+    pub async fn resolve_post_uris(&self, posts: &[String]) -> Result<Vec<AtUri>> {
+        let mut dids: HashMap<String, String> = HashMap::new();
+        let mut uris = Vec::with_capacity(posts.len());
+
+        for post in posts {
+            let mut uri = AtUri::parse(post)?.expect(aturi::POST)?;
+            if !uri.has_did() {
+                let did = match dids.get(&uri.authority) {
+                    Some(did) => did.clone(),
+                    None => {
+                        let did = self.resolve_handle(&uri.authority).await?;
+                        dids.insert(uri.authority.clone(), did.clone());
+                        did
+                    }
+                };
+                uri.authority = did;
             }
+            uris.push(uri);
+        }
+
+        Ok(uris)
+    }
+
+    /// Replaces a handle in `uri` with the DID it resolves to, as XRPC
+    /// endpoints only accept at:// URIs with a DID authority.
+    pub async fn resolve_at_uri(&self, uri: AtUri) -> Result<AtUri> {
+        if uri.has_did() {
+            return Ok(uri);
         }
+        Ok(AtUri {
+            authority: self.resolve_handle(&uri.authority).await?,
+            ..uri
+        })
+    }
 
-        pb.finish_with_message(format!("Fetched {} image posts", all_posts.len()));
-        Ok(all_posts)
+    /// Looks up the PDS hosting `did` from its DID document.
+    pub async fn resolve_pds(&self, did: &str) -> Result<String> {
+        self.identity.resolve_pds(did).await
     }
 
     pub fn get_image_url(&self, did: &str, cid: &str) -> String {
+        self.get_blob_url(&self.endpoints.blob_host, did, cid)
+    }
+
+    pub fn get_blob_url(&self, service: &str, did: &str, cid: &str) -> String {
+        format!(
+            "{}?did={}&cid={}",
+            xrpc_url(service, "com.atproto.sync.getBlob"),
+            did,
+            cid
+        )
+    }
+
+    /// URL of the HLS master playlist the video service serves for a video blob.
+    pub fn get_video_playlist_url(&self, did: &str, cid: &str) -> String {
         format!(
-            "https://bsky.social/xrpc/com.atproto.sync.getBlob?did={}&cid={}",
-            did, cid
+            "{}/watch/{}/{}/playlist.m3u8",
+            self.endpoints.video.trim_end_matches('/'),
+            did.replace(':', "%3A"),
+            cid
         )
     }
 
+    /// Downloads an HLS stream and joins its segments into a single file.
+    ///
+    /// `playlist_url` may be a master playlist, in which case the
+    /// highest-bandwidth variant is used. Returns the stream along with the
+    /// media playlist it was assembled from.
+    pub async fn download_hls(&self, playlist_url: &str) -> Result<(Vec<u8>, MediaPlaylist)> {
+        let (media_url, media) = self.hls_media_playlist(playlist_url).await?;
+
+        let mut bytes = Vec::new();
+        for uri in media.init.iter().chain(media.segments.iter()) {
+            let segment_url = hls::resolve(&media_url, uri)?;
+            bytes.extend(self.download(&segment_url, "video segment").await?);
+        }
+
+        Ok((bytes, media))
+    }
+
+    /// Reads a video's duration in seconds from its HLS playlist.
+    pub async fn hls_duration(&self, playlist_url: &str) -> Result<f64> {
+        Ok(self.hls_media_playlist(playlist_url).await?.1.duration)
+    }
+
+    /// Fetches the media playlist for `playlist_url`, following a master
+    /// playlist to its best variant.
+    async fn hls_media_playlist(&self, playlist_url: &str) -> Result<(String, MediaPlaylist)> {
+        let mut url = playlist_url.to_string();
+        let mut playlist = self.download_text(&url).await?;
+
+        if hls::is_master(&playlist) {
+            let variant = hls::best_variant(&playlist)
+                .ok_or_else(|| anyhow!("HLS master playlist lists no variants"))?;
+            url = hls::resolve(&url, &variant.uri)?;
+            playlist = self.download_text(&url).await?;
+        }
+
+        Ok((url.clone(), hls::parse_media(&playlist)?))
+    }
+
+    async fn download_text(&self, url: &str) -> Result<String> {
+        let bytes = self.download(url, "playlist").await?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
     pub async fn download_image(&self, url: &str) -> Result<Vec<u8>> {
-        let session = self
-            .session
-            .as_ref()
-            .ok_or_else(|| anyhow!("Not authenticated"))?;
+        self.download(url, "image").await
+    }
 
+    /// Fetches `url`, retrying transient failures; `what` names the content in errors.
+    ///
+    /// getBlob is public, so downloads are always sent without credentials.
+    /// OAuth tokens are bound to the account's PDS and would be rejected (and
+    /// needlessly refreshed) by any other host, such as the fallback blob host.
+    pub async fn download(&self, url: &str, what: &str) -> Result<Vec<u8>> {
         let response = self
-            .http
-            .get(url)
-            .bearer_auth(&session.access_jwt)
-            .send()
+            .retry
+            .send(&format!("Downloading {}", what), || async {
+                self.execute(self.http.get(url).build()?).await
+            })
             .await?;
 
         if !response.status().is_success() {
-            return Err(anyhow!("Failed to download image: {}", response.status()));
+            return Err(DownloadFailed {
+                what: what.to_string(),
+                status: response.status(),
+            }
+            .into());
         }
 
         let bytes = response.bytes().await?;
         Ok(bytes.to_vec())
     }
+
+    /// Streams a file a post links to, such as a GIF from the GIF picker, into
+    /// `path` and returns its size.
+    ///
+    /// Links can point at any host, so unlike blobs the response must say it
+    /// is an image or video, and it is written to disk as it arrives and
+    /// abandoned past `max_bytes` instead of being held in memory.
+    pub async fn download_linked_media(
+        &self,
+        url: &str,
+        path: &Path,
+        max_bytes: u64,
+    ) -> Result<u64> {
+        let response = self
+            .retry
+            .send("Downloading linked media", || async {
+                Ok(self.http.get(url).send().await?)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(DownloadFailed {
+                what: "linked media".to_string(),
+                status: response.status(),
+            }
+            .into());
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if !content_type.starts_with("image/") && !content_type.starts_with("video/") {
+            return Err(anyhow!(
+                "Linked media is not an image or video (Content-Type {:?})",
+                content_type
+            ));
+        }
+        if let Some(length) = response.content_length().filter(|&len| len > max_bytes) {
+            return Err(anyhow!(
+                "Linked media is {} bytes, over the limit of {}",
+                length,
+                max_bytes
+            ));
+        }
+
+        let result = stream_to_file(response, path, max_bytes).await;
+        if result.is_err() {
+            // Don't leave a partial file behind
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        result
+    }
+}
+
+/// Writes a response body to `path` as it arrives, failing once it grows past
+/// `max_bytes`.
+async fn stream_to_file(response: Response, path: &Path, max_bytes: u64) -> Result<u64> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut size = 0;
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.try_next().await? {
+        size += chunk.len() as u64;
+        if size > max_bytes {
+            return Err(anyhow!(
+                "Linked media is over the limit of {} bytes",
+                max_bytes
+            ));
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(size)
 }
@@ -1,8 +1,9 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct Database {
@@ -34,9 +35,84 @@ pub struct ArchivedImage {
     pub downloaded_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedVideo {
+    pub id: i64,
+    pub post_uri: String,
+    pub blob_cid: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub size: i64,
+    /// Length in seconds, when the HLS playlist could be read
+    pub duration: Option<f64>,
+    pub aspect_width: Option<u32>,
+    pub aspect_height: Option<u32>,
+    pub alt_text: Option<String>,
+    /// "blob" when downloaded with getBlob, "hls" when assembled from the playlist
+    pub source: String,
+    pub downloaded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedCaption {
+    pub video_cid: String,
+    pub lang: String,
+    pub blob_cid: String,
+    pub filename: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedExternal {
+    pub post_uri: String,
+    pub uri: String,
+    pub title: String,
+    pub description: String,
+    pub thumb_cid: Option<String>,
+    pub thumb_filename: Option<String>,
+    /// Linked GIF or media file, when the link pointed at one
+    pub media_filename: Option<String>,
+    pub archived_at: DateTime<Utc>,
+}
+
+/// Where a post sits in its thread. Stored for every post of an archived
+/// thread, including posts without media, so the thread can be rebuilt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThreadLink {
+    pub post_uri: String,
+    /// The post this one replies to; `None` for the first post of the thread
+    pub parent_uri: Option<String>,
+    /// First post of the thread (the post itself, for the first post)
+    pub root_uri: String,
+}
+
+/// A repost of an archived post, e.g. by a curator who reposts to save.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedRepost {
+    pub post_uri: String,
+    pub reposted_by_did: String,
+    pub reposted_by_handle: String,
+    /// When the repost was made, as reported by the AppView
+    pub reposted_at: String,
+}
+
+/// A download that failed for good, e.g. a blob deleted from its PDS. Kept so
+/// resuming can move past the post while the failure stays on record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailedBlob {
+    pub post_uri: String,
+    /// Blob CID, or the URL of linked media
+    pub blob: String,
+    /// What the blob is, e.g. "image" or "thumbnail"
+    pub what: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
 impl Database {
     pub fn new(path: &Path) -> Result<Self> {
         let conn = Connection::open(path)?;
+        // Concurrent archiving opens several connections to the same file
+        conn.busy_timeout(Duration::from_secs(30))?;
 
         let db = Self { conn };
         db.create_tables()?;
@@ -75,6 +151,132 @@ impl Database {
             [],
         )?;
 
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS pds_endpoints (
+                did TEXT PRIMARY KEY,
+                pds_url TEXT NOT NULL,
+                resolved_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS archived_videos (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                post_uri TEXT NOT NULL,
+                blob_cid TEXT NOT NULL UNIQUE,
+                filename TEXT NOT NULL,
+                mime_type TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                duration REAL,
+                aspect_width INTEGER,
+                aspect_height INTEGER,
+                alt_text TEXT,
+                source TEXT NOT NULL,
+                downloaded_at TEXT NOT NULL,
+                FOREIGN KEY (post_uri) REFERENCES archived_posts(uri)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS video_captions (
+                video_cid TEXT NOT NULL,
+                lang TEXT NOT NULL,
+                blob_cid TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                PRIMARY KEY (video_cid, lang),
+                FOREIGN KEY (video_cid) REFERENCES archived_videos(blob_cid)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS archived_externals (
+                post_uri TEXT PRIMARY KEY,
+                uri TEXT NOT NULL,
+                title TEXT NOT NULL,
+                description TEXT NOT NULL,
+                thumb_cid TEXT,
+                thumb_filename TEXT,
+                media_filename TEXT,
+                archived_at TEXT NOT NULL,
+                FOREIGN KEY (post_uri) REFERENCES archived_posts(uri)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS post_quotes (
+                post_uri TEXT NOT NULL,
+                quoted_uri TEXT NOT NULL,
+                PRIMARY KEY (post_uri, quoted_uri),
+                FOREIGN KEY (post_uri) REFERENCES archived_posts(uri),
+                FOREIGN KEY (quoted_uri) REFERENCES archived_posts(uri)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS post_sources (
+                post_uri TEXT NOT NULL,
+                source TEXT NOT NULL,
+                first_seen_at TEXT NOT NULL,
+                PRIMARY KEY (post_uri, source),
+                FOREIGN KEY (post_uri) REFERENCES archived_posts(uri)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS post_threads (
+                post_uri TEXT PRIMARY KEY,
+                parent_uri TEXT,
+                root_uri TEXT NOT NULL,
+                recorded_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_thread_root ON post_threads(root_uri)",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS reposts (
+                post_uri TEXT NOT NULL,
+                reposted_by_did TEXT NOT NULL,
+                reposted_by_handle TEXT NOT NULL,
+                reposted_at TEXT NOT NULL,
+                PRIMARY KEY (post_uri, reposted_by_did),
+                FOREIGN KEY (post_uri) REFERENCES archived_posts(uri)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS failed_blobs (
+                post_uri TEXT NOT NULL,
+                blob TEXT NOT NULL,
+                what TEXT NOT NULL,
+                error TEXT NOT NULL,
+                failed_at TEXT NOT NULL,
+                PRIMARY KEY (post_uri, blob)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS account_cursors (
+                did TEXT PRIMARY KEY,
+                handle TEXT NOT NULL,
+                cursor TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
         self.conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_post_uri ON archived_images(post_uri)",
             [],
@@ -148,6 +350,352 @@ impl Database {
         Ok(())
     }
 
+    pub fn is_video_archived(&self, blob_cid: &str) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM archived_videos WHERE blob_cid = ?1",
+            params![blob_cid],
+            |row| row.get(0),
+        )?;
+
+        Ok(count > 0)
+    }
+
+    pub fn save_video(&self, video: &ArchivedVideo) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO archived_videos
+             (post_uri, blob_cid, filename, mime_type, size, duration, aspect_width, aspect_height, alt_text, source, downloaded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                video.post_uri,
+                video.blob_cid,
+                video.filename,
+                video.mime_type,
+                video.size,
+                video.duration,
+                video.aspect_width,
+                video.aspect_height,
+                video.alt_text,
+                video.source,
+                video.downloaded_at.to_rfc3339(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_video(&self, blob_cid: &str) -> Result<Option<ArchivedVideo>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, post_uri, blob_cid, filename, mime_type, size, duration, aspect_width, aspect_height, alt_text, source, downloaded_at
+                 FROM archived_videos WHERE blob_cid = ?1",
+                params![blob_cid],
+                |row| {
+                    let downloaded_at: String = row.get(11)?;
+                    Ok(ArchivedVideo {
+                        id: row.get(0)?,
+                        post_uri: row.get(1)?,
+                        blob_cid: row.get(2)?,
+                        filename: row.get(3)?,
+                        mime_type: row.get(4)?,
+                        size: row.get(5)?,
+                        duration: row.get(6)?,
+                        aspect_width: row.get(7)?,
+                        aspect_height: row.get(8)?,
+                        alt_text: row.get(9)?,
+                        source: row.get(10)?,
+                        downloaded_at: DateTime::parse_from_rfc3339(&downloaded_at)
+                            .map(|d| d.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now()),
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    pub fn save_caption(&self, caption: &ArchivedCaption) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO video_captions (video_cid, lang, blob_cid, filename)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                caption.video_cid,
+                caption.lang,
+                caption.blob_cid,
+                caption.filename
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn is_external_archived(&self, post_uri: &str) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM archived_externals WHERE post_uri = ?1",
+            params![post_uri],
+            |row| row.get(0),
+        )?;
+
+        Ok(count > 0)
+    }
+
+    pub fn save_external(&self, external: &ArchivedExternal) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO archived_externals
+             (post_uri, uri, title, description, thumb_cid, thumb_filename, media_filename, archived_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                external.post_uri,
+                external.uri,
+                external.title,
+                external.description,
+                external.thumb_cid,
+                external.thumb_filename,
+                external.media_filename,
+                external.archived_at.to_rfc3339(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_external(&self, post_uri: &str) -> Result<Option<ArchivedExternal>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT post_uri, uri, title, description, thumb_cid, thumb_filename, media_filename, archived_at
+                 FROM archived_externals WHERE post_uri = ?1",
+                params![post_uri],
+                |row| {
+                    let archived_at: String = row.get(7)?;
+                    Ok(ArchivedExternal {
+                        post_uri: row.get(0)?,
+                        uri: row.get(1)?,
+                        title: row.get(2)?,
+                        description: row.get(3)?,
+                        thumb_cid: row.get(4)?,
+                        thumb_filename: row.get(5)?,
+                        media_filename: row.get(6)?,
+                        archived_at: DateTime::parse_from_rfc3339(&archived_at)
+                            .map(|d| d.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now()),
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Records that `post_uri` quotes `quoted_uri`. Both posts must already be saved.
+    pub fn save_quote(&self, post_uri: &str, quoted_uri: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO post_quotes (post_uri, quoted_uri) VALUES (?1, ?2)",
+            params![post_uri, quoted_uri],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the URIs of the posts `post_uri` quotes.
+    pub fn get_quoted_posts(&self, post_uri: &str) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT quoted_uri FROM post_quotes WHERE post_uri = ?1")?;
+        let uris = stmt
+            .query_map(params![post_uri], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(uris)
+    }
+
+    /// Records that a post was found through `source` (e.g. "timeline").
+    pub fn save_post_source(&self, post_uri: &str, source: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO post_sources (post_uri, source, first_seen_at)
+             VALUES (?1, ?2, ?3)",
+            params![post_uri, source, Utc::now().to_rfc3339()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_post_sources(&self, post_uri: &str) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT source FROM post_sources WHERE post_uri = ?1 ORDER BY source")?;
+        let sources = stmt
+            .query_map(params![post_uri], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(sources)
+    }
+
+    /// Returns the cached PDS for `did` along with when it was resolved.
+    pub fn get_pds_endpoint(&self, did: &str) -> Result<Option<(String, DateTime<Utc>)>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT pds_url, resolved_at FROM pds_endpoints WHERE did = ?1",
+                params![did],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        Ok(row.and_then(|(pds_url, resolved_at)| {
+            DateTime::parse_from_rfc3339(&resolved_at)
+                .ok()
+                .map(|resolved_at| (pds_url, resolved_at.with_timezone(&Utc)))
+        }))
+    }
+
+    pub fn save_pds_endpoint(&self, did: &str, pds_url: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO pds_endpoints (did, pds_url, resolved_at)
+             VALUES (?1, ?2, ?3)",
+            params![did, pds_url, Utc::now().to_rfc3339()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn save_thread_link(&self, link: &ThreadLink) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO post_threads (post_uri, parent_uri, root_uri, recorded_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                link.post_uri,
+                link.parent_uri,
+                link.root_uri,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Every recorded post of the thread starting at `root_uri`, root included.
+    pub fn get_thread(&self, root_uri: &str) -> Result<Vec<ThreadLink>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT post_uri, parent_uri, root_uri FROM post_threads
+             WHERE root_uri = ?1 ORDER BY post_uri",
+        )?;
+        let links = stmt
+            .query_map(params![root_uri], |row| {
+                Ok(ThreadLink {
+                    post_uri: row.get(0)?,
+                    parent_uri: row.get(1)?,
+                    root_uri: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(links)
+    }
+
+    pub fn save_repost(&self, repost: &ArchivedRepost) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO reposts
+             (post_uri, reposted_by_did, reposted_by_handle, reposted_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                repost.post_uri,
+                repost.reposted_by_did,
+                repost.reposted_by_handle,
+                repost.reposted_at,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_reposts(&self, post_uri: &str) -> Result<Vec<ArchivedRepost>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT post_uri, reposted_by_did, reposted_by_handle, reposted_at FROM reposts
+             WHERE post_uri = ?1 ORDER BY reposted_at",
+        )?;
+        let reposts = stmt
+            .query_map(params![post_uri], |row| {
+                Ok(ArchivedRepost {
+                    post_uri: row.get(0)?,
+                    reposted_by_did: row.get(1)?,
+                    reposted_by_handle: row.get(2)?,
+                    reposted_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(reposts)
+    }
+
+    pub fn save_failed_blob(&self, failed: &FailedBlob) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO failed_blobs (post_uri, blob, what, error, failed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                failed.post_uri,
+                failed.blob,
+                failed.what,
+                failed.error,
+                failed.failed_at.to_rfc3339(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_failed_blobs(&self, post_uri: &str) -> Result<Vec<FailedBlob>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT post_uri, blob, what, error, failed_at FROM failed_blobs
+             WHERE post_uri = ?1 ORDER BY blob",
+        )?;
+        let failed = stmt
+            .query_map(params![post_uri], |row| {
+                let failed_at: String = row.get(4)?;
+                Ok(FailedBlob {
+                    post_uri: row.get(0)?,
+                    blob: row.get(1)?,
+                    what: row.get(2)?,
+                    error: row.get(3)?,
+                    failed_at: DateTime::parse_from_rfc3339(&failed_at)
+                        .map(|d| d.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(failed)
+    }
+
+    /// Resume cursor into an account's posts, saved while archiving follows.
+    pub fn get_account_cursor(&self, did: &str) -> Result<Option<String>> {
+        let cursor = self
+            .conn
+            .query_row(
+                "SELECT cursor FROM account_cursors WHERE did = ?1",
+                params![did],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(cursor)
+    }
+
+    pub fn save_account_cursor(&self, did: &str, handle: &str, cursor: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO account_cursors (did, handle, cursor, updated_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![did, handle, cursor, Utc::now().to_rfc3339()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Forgets an account's cursor once all of its posts are archived.
+    pub fn clear_account_cursor(&self, did: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM account_cursors WHERE did = ?1", params![did])?;
+
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub fn get_stats(&self) -> Result<(i64, i64)> {
         let post_count: i64 =
@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+
+/// One rendition listed in an HLS master playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub bandwidth: u64,
+    pub uri: String,
+}
+
+/// The segments of an HLS media playlist, in playback order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaPlaylist {
+    /// Initialization segment from `#EXT-X-MAP`, for fragmented MP4 streams
+    pub init: Option<String>,
+    pub segments: Vec<String>,
+    /// Sum of the `#EXTINF` durations, in seconds
+    pub duration: f64,
+}
+
+impl MediaPlaylist {
+    /// Container the joined segments form: fragmented MP4 when there is an
+    /// initialization segment, MPEG-TS otherwise.
+    pub fn mime_type(&self) -> &'static str {
+        if self.init.is_some() {
+            "video/mp4"
+        } else {
+            "video/mp2t"
+        }
+    }
+}
+
+/// Whether `playlist` is a master playlist listing variants rather than segments.
+pub fn is_master(playlist: &str) -> bool {
+    playlist.contains("#EXT-X-STREAM-INF")
+}
+
+/// Lists the variants of a master playlist.
+pub fn parse_master(playlist: &str) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let mut lines = playlist.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        if let Some(attributes) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            let bandwidth = attribute(attributes, "BANDWIDTH")
+                .and_then(|b| b.parse().ok())
+                .unwrap_or(0);
+            // The variant's URI is the next line that isn't a tag or blank
+            if let Some(uri) = lines.find(|l| !l.is_empty() && !l.starts_with('#')) {
+                variants.push(Variant {
+                    bandwidth,
+                    uri: uri.to_string(),
+                });
+            }
+        }
+    }
+
+    variants
+}
+
+/// Picks the highest-bandwidth variant of a master playlist.
+pub fn best_variant(playlist: &str) -> Option<Variant> {
+    parse_master(playlist)
+        .into_iter()
+        .max_by_key(|variant| variant.bandwidth)
+}
+
+/// Lists the segments of a media playlist.
+pub fn parse_media(playlist: &str) -> Result<MediaPlaylist> {
+    let mut media = MediaPlaylist::default();
+
+    for line in playlist.lines().map(str::trim) {
+        if let Some(map) = line.strip_prefix("#EXT-X-MAP:") {
+            media.init = attribute(map, "URI");
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let seconds = info.split(',').next().unwrap_or("");
+            media.duration += seconds.trim().parse::<f64>().unwrap_or(0.0);
+        } else if !line.is_empty() && !line.starts_with('#') {
+            media.segments.push(line.to_string());
+        }
+    }
+
+    if media.segments.is_empty() {
+        return Err(anyhow!("HLS playlist has no segments"));
+    }
+
+    Ok(media)
+}
+
+/// Resolves a playlist entry against the URL of the playlist it came from.
+pub fn resolve(base: &str, uri: &str) -> Result<String> {
+    Ok(Url::parse(base)?.join(uri)?.to_string())
+}
+
+/// Reads `NAME=value` or `NAME="value"` from an attribute list.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while !rest.is_empty() {
+        let (key, after_key) = rest.split_once('=')?;
+        let (value, after_value) = match after_key.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, after) = quoted.split_once('"')?;
+                (value, after.strip_prefix(',').unwrap_or(after))
+            }
+            None => after_key.split_once(',').unwrap_or((after_key, "")),
+        };
+        if key.trim() == name {
+            return Some(value.to_string());
+        }
+        rest = after_value;
+    }
+    None
+}
@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use tracing::debug;
+
+pub const DEFAULT_PLC_DIRECTORY: &str = "https://plc.directory";
+
+/// Resolves DIDs to their DID documents (did:plc and did:web).
+#[derive(Debug, Clone)]
+pub struct IdentityResolver {
+    http: HttpClient,
+    plc_directory: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DidDocument {
+    pub id: String,
+    #[serde(rename = "alsoKnownAs", default)]
+    pub also_known_as: Vec<String>,
+    #[serde(default)]
+    pub service: Vec<Service>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Service {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(rename = "serviceEndpoint")]
+    pub service_endpoint: serde_json::Value,
+}
+
+impl DidDocument {
+    /// Returns the `#atproto_pds` service endpoint, if the document declares one.
+    pub fn pds_endpoint(&self) -> Option<&str> {
+        self.service
+            .iter()
+            .find(|service| {
+                // The id may be relative ("#atproto_pds") or fully qualified ("did:...#atproto_pds")
+                service.id.ends_with("#atproto_pds") && service.type_ == "AtprotoPersonalDataServer"
+            })
+            .and_then(|service| service.service_endpoint.as_str())
+            .map(|endpoint| endpoint.trim_end_matches('/'))
+    }
+}
+
+/// Builds the URL of the DID document for a did:web identifier.
+///
+/// `did:web:example.com` maps to `https://example.com/.well-known/did.json`,
+/// while `did:web:example.com:user:alice` maps to `https://example.com/user/alice/did.json`.
+pub fn did_web_url(did: &str) -> Result<String> {
+    let id = did
+        .strip_prefix("did:web:")
+        .ok_or_else(|| anyhow!("Not a did:web identifier: {}", did))?;
+
+    let mut parts = id.split(':');
+    let host = parts
+        .next()
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| anyhow!("Missing host in {}", did))?
+        .replace("%3A", ":")
+        .replace("%3a", ":");
+    let path: Vec<&str> = parts.collect();
+
+    if path.is_empty() {
+        Ok(format!("https://{}/.well-known/did.json", host))
+    } else {
+        Ok(format!("https://{}/{}/did.json", host, path.join("/")))
+    }
+}
+
+impl IdentityResolver {
+    pub fn new(http: HttpClient, plc_directory: &str) -> Self {
+        Self {
+            http,
+            plc_directory: plc_directory.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub async fn resolve_did(&self, did: &str) -> Result<DidDocument> {
+        let url = if did.starts_with("did:plc:") {
+            format!("{}/{}", self.plc_directory, did)
+        } else if did.starts_with("did:web:") {
+            did_web_url(did)?
+        } else {
+            return Err(anyhow!("Unsupported DID method: {}", did));
+        };
+
+        debug!("Resolving {} via {}", did, url);
+        let response = self.http.get(&url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to resolve {}: {}", did, response.status()));
+        }
+
+        let document: DidDocument = response.json().await?;
+        if document.id != did {
+            return Err(anyhow!(
+                "DID document id {} does not match {}",
+                document.id,
+                did
+            ));
+        }
+
+        Ok(document)
+    }
+
+    pub async fn resolve_pds(&self, did: &str) -> Result<String> {
+        let document = self.resolve_did(did).await?;
+        document
+            .pds_endpoint()
+            .map(|endpoint| endpoint.to_string())
+            .ok_or_else(|| anyhow!("No #atproto_pds service in DID document for {}", did))
+    }
+}
@@ -0,0 +1,438 @@
+//! Typed models for the app.bsky lexicons the archiver reads.
+//!
+//! Unions are dispatched on `$type`. Types added to the lexicons later parse
+//! as an `Unknown` variant, and a malformed embed, reply or feed reason is logged
+//! and dropped rather than failing the whole page it came in.
+
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// app.bsky.feed.defs#postView
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Post {
+    pub uri: String,
+    pub cid: String,
+    pub author: Author,
+    pub record: Record,
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+    pub labels: Option<Vec<Label>>,
+    /// Hydrated view of the embed, which includes the content of quoted posts
+    #[serde(default, deserialize_with = "lenient")]
+    pub embed: Option<EmbedView>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Label {
+    pub src: String,
+    pub uri: String,
+    pub val: String,
+    #[serde(rename = "cts")]
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Author {
+    pub did: String,
+    pub handle: String,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+}
+
+/// An app.bsky.feed.post record.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[allow(dead_code)]
+pub struct Record {
+    #[serde(rename = "$type", default)]
+    pub record_type: String,
+    pub text: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub embed: Option<Embed>,
+    #[serde(rename = "createdAt", default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub langs: Vec<String>,
+    /// Set on replies
+    #[serde(default, deserialize_with = "lenient")]
+    pub reply: Option<ReplyRef>,
+}
+
+/// The posts a reply answers: the one directly above it, and the first
+/// post of the thread.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplyRef {
+    pub root: StrongRef,
+    pub parent: StrongRef,
+}
+
+/// Media or a quoted record attached to a post record.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "$type")]
+pub enum Embed {
+    #[serde(rename = "app.bsky.embed.images")]
+    Images { images: Vec<Image> },
+    #[serde(rename = "app.bsky.embed.external")]
+    External { external: External },
+    #[serde(rename = "app.bsky.embed.video")]
+    Video(Video),
+    /// A quote post (app.bsky.embed.record)
+    #[serde(rename = "app.bsky.embed.record")]
+    Record { record: StrongRef },
+    /// A quote post with its own media attached (app.bsky.embed.recordWithMedia)
+    #[serde(rename = "app.bsky.embed.recordWithMedia")]
+    RecordWithMedia {
+        record: RecordEmbed,
+        media: Box<Embed>,
+    },
+    /// An embed type this version doesn't know about
+    #[serde(other)]
+    Unknown,
+}
+
+impl Embed {
+    /// Images attached directly to the post, including the media half of a
+    /// quote post. Images inside the quoted post are not included.
+    pub fn images(&self) -> &[Image] {
+        match self {
+            Embed::Images { images } => images,
+            Embed::RecordWithMedia { media, .. } => media.images(),
+            _ => &[],
+        }
+    }
+
+    /// Video attached directly to the post, including the media half of a quote post.
+    pub fn video(&self) -> Option<&Video> {
+        match self {
+            Embed::Video(video) => Some(video),
+            Embed::RecordWithMedia { media, .. } => media.video(),
+            _ => None,
+        }
+    }
+
+    /// Link card attached directly to the post, including the media half of a quote post.
+    pub fn external(&self) -> Option<&External> {
+        match self {
+            Embed::External { external } => Some(external),
+            Embed::RecordWithMedia { media, .. } => media.external(),
+            _ => None,
+        }
+    }
+
+    /// Whether the post has images or a video of its own.
+    pub fn has_media(&self) -> bool {
+        !self.images().is_empty() || self.video().is_some()
+    }
+}
+
+/// The record half of a recordWithMedia embed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordEmbed {
+    pub record: StrongRef,
+}
+
+/// com.atproto.repo.strongRef
+#[derive(Debug, Clone, Deserialize)]
+pub struct StrongRef {
+    pub uri: String,
+    pub cid: String,
+}
+
+/// An app.bsky.embed.video record.
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Video {
+    pub video: View,
+    #[serde(default)]
+    pub captions: Vec<Caption>,
+    pub alt: Option<String>,
+    #[serde(rename = "aspectRatio")]
+    pub aspect_ratio: Option<AspectRatio>,
+}
+
+/// A subtitle track (WebVTT) attached to a video.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Caption {
+    pub lang: String,
+    pub file: View,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct Image {
+    pub alt: Option<String>,
+    pub fullsize: Option<String>,
+    pub thumb: Option<String>,
+    #[serde(rename = "aspectRatio")]
+    pub aspect_ratio: Option<AspectRatio>,
+    pub image: View,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A blob reference.
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct View {
+    #[serde(rename = "$type")]
+    pub type_: String,
+    #[serde(rename = "ref")]
+    pub ref_: BlobRef,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct BlobRef {
+    #[serde(rename = "$link")]
+    pub link: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct External {
+    pub uri: String,
+    pub title: String,
+    pub description: String,
+    /// Preview image of a link card
+    pub thumb: Option<View>,
+}
+
+/// The hydrated form of an embed, as found on a post view.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "$type")]
+#[allow(dead_code)]
+pub enum EmbedView {
+    #[serde(rename = "app.bsky.embed.images#view")]
+    Images { images: Vec<ImageView> },
+    #[serde(rename = "app.bsky.embed.external#view")]
+    External { external: ExternalView },
+    #[serde(rename = "app.bsky.embed.video#view")]
+    Video(VideoView),
+    #[serde(rename = "app.bsky.embed.record#view")]
+    Record { record: ViewRecord },
+    #[serde(rename = "app.bsky.embed.recordWithMedia#view")]
+    RecordWithMedia {
+        record: RecordView,
+        media: Box<EmbedView>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct ImageView {
+    pub thumb: String,
+    pub fullsize: String,
+    #[serde(default)]
+    pub alt: String,
+    #[serde(rename = "aspectRatio")]
+    pub aspect_ratio: Option<AspectRatio>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct ExternalView {
+    pub uri: String,
+    pub title: String,
+    pub description: String,
+    pub thumb: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct VideoView {
+    pub cid: String,
+    pub playlist: String,
+    pub thumbnail: Option<String>,
+    pub alt: Option<String>,
+    #[serde(rename = "aspectRatio")]
+    pub aspect_ratio: Option<AspectRatio>,
+}
+
+/// The record half of a recordWithMedia view.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordView {
+    pub record: ViewRecord,
+}
+
+/// What a quote embed points at, once hydrated.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "$type")]
+#[allow(dead_code)]
+pub enum ViewRecord {
+    /// A quoted post
+    #[serde(rename = "app.bsky.embed.record#viewRecord")]
+    Post(Box<QuotedPost>),
+    #[serde(rename = "app.bsky.embed.record#viewNotFound")]
+    NotFound { uri: String },
+    #[serde(rename = "app.bsky.embed.record#viewBlocked")]
+    Blocked { uri: String },
+    #[serde(rename = "app.bsky.embed.record#viewDetached")]
+    Detached { uri: String },
+    /// Feeds, lists, starter packs and anything newer
+    #[serde(other)]
+    Unknown,
+}
+
+/// app.bsky.embed.record#viewRecord
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub struct QuotedPost {
+    pub uri: String,
+    pub cid: String,
+    pub author: Author,
+    pub value: Record,
+    pub labels: Option<Vec<Label>>,
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+    #[serde(default)]
+    pub embeds: Vec<EmbedView>,
+}
+
+/// Why a post appears in a feed other than being written by the feed's owner.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "$type")]
+#[allow(dead_code)]
+pub enum FeedReason {
+    #[serde(rename = "app.bsky.feed.defs#reasonRepost")]
+    Repost(ReasonRepost),
+    #[serde(rename = "app.bsky.feed.defs#reasonPin")]
+    Pin,
+    #[serde(other)]
+    Unknown,
+}
+
+/// app.bsky.feed.defs#reasonRepost
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReasonRepost {
+    /// Account that reposted the post
+    pub by: Author,
+    /// When the repost was indexed, which is the closest thing to its creation time
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+}
+
+/// A node of the thread returned by app.bsky.feed.getPostThread.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "$type")]
+#[allow(dead_code)]
+pub enum ThreadView {
+    #[serde(rename = "app.bsky.feed.defs#threadViewPost")]
+    Post(Box<ThreadViewPost>),
+    #[serde(rename = "app.bsky.feed.defs#notFoundPost")]
+    NotFound { uri: String },
+    #[serde(rename = "app.bsky.feed.defs#blockedPost")]
+    Blocked { uri: String },
+    #[serde(other)]
+    Unknown,
+}
+
+/// app.bsky.feed.defs#threadViewPost
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThreadViewPost {
+    pub post: Post,
+    pub parent: Option<ThreadView>,
+    #[serde(default)]
+    pub replies: Vec<ThreadView>,
+}
+
+impl ThreadView {
+    /// Every post in the thread, from the topmost parent down, with each
+    /// post followed by its replies. Missing and blocked posts are left out.
+    pub fn into_posts(self) -> Vec<Post> {
+        let mut posts = Vec::new();
+        if let ThreadView::Post(view) = self {
+            let ThreadViewPost {
+                post,
+                parent,
+                replies,
+            } = *view;
+            if let Some(parent) = parent {
+                posts.extend(parent.into_posts());
+            }
+            posts.push(post);
+            for reply in replies {
+                posts.extend(reply.into_posts());
+            }
+        }
+        posts
+    }
+}
+
+impl Post {
+    pub fn has_nsfw_labels(&self) -> bool {
+        if let Some(labels) = &self.labels {
+            labels.iter().any(|label| {
+                matches!(
+                    label.val.as_str(),
+                    "porn"
+                        | "sexual"
+                        | "nudity"
+                        | "graphic-media"
+                        | "self-harm"
+                        | "sensitive"
+                        | "content-warning"
+                )
+            })
+        } else {
+            false
+        }
+    }
+
+    /// The post this one quotes, built from the hydrated embed view.
+    ///
+    /// Returns `None` if the post isn't a quote, or the quoted record is
+    /// missing, blocked or not a post (e.g. a quoted feed or list).
+    pub fn quoted_post(&self) -> Option<Post> {
+        let record = match self.embed.as_ref()? {
+            EmbedView::Record { record } => record,
+            EmbedView::RecordWithMedia { record, .. } => &record.record,
+            _ => return None,
+        };
+        let ViewRecord::Post(quoted) = record else {
+            return None;
+        };
+
+        Some(Post {
+            uri: quoted.uri.clone(),
+            cid: quoted.cid.clone(),
+            author: quoted.author.clone(),
+            record: quoted.value.clone(),
+            indexed_at: quoted.indexed_at.clone(),
+            labels: quoted.labels.clone(),
+            embed: quoted.embeds.first().cloned(),
+        })
+    }
+}
+
+/// Parses an optional union member or reference, logging and dropping it if
+/// it's malformed instead of failing the value that contains it.
+pub(crate) fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| {
+        let union_type = value
+            .get("$type")
+            .and_then(|t| t.as_str())
+            .unwrap_or("untyped")
+            .to_string();
+        serde_json::from_value(value)
+            .inspect_err(|e| warn!("Ignoring malformed {}: {}", union_type, e))
+            .ok()
+    }))
+}
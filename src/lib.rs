@@ -1,3 +1,13 @@
 pub mod archive;
+pub mod aturi;
 pub mod bluesky;
 pub mod database;
+pub mod hls;
+pub mod identity;
+pub mod lexicon;
+pub mod oauth;
+pub mod paginator;
+pub mod ratelimit;
+pub mod retry;
+pub mod search;
+pub mod session;
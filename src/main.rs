@@ -1,27 +1,59 @@
-use anyhow::Result;
-use clap::Parser;
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use clap::{ArgGroup, Parser};
+use futures::{stream, Stream, StreamExt};
+use indicatif::MultiProgress;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tracing::{info, warn};
 
-mod archive;
-mod bluesky;
-mod database;
+use bluesky_archiver::archive::{ArchiveItem, ArchiveStats, Archiver};
+use bluesky_archiver::bluesky::Author;
+use bluesky_archiver::paginator::{CursorCallback, FeedPage, PaginateOptions};
+use bluesky_archiver::search::SearchQuery;
+use bluesky_archiver::{bluesky, database, identity, session};
 
 #[derive(Parser, Debug)]
 #[command(name = "bluesky-archiver")]
 #[command(about = "Archive liked image posts from Bluesky", long_about = None)]
+#[command(group(
+    ArgGroup::new("source").args(["archive_user", "archive_reposts", "feed", "list_feed", "list_members", "search", "archive_follows", "timeline", "thread", "posts", "posts_file"])
+))]
+// Every filter also has `requires = "search"`, but clap lets that through when
+// another source is given (it conflicts with --search), so rule that out here
+#[command(group(
+    ArgGroup::new("search_filters")
+        .args(["search_author", "search_lang", "since", "until", "sort", "tags"])
+        .multiple(true)
+        .conflicts_with_all(["archive_user", "archive_reposts", "feed", "list_feed", "list_members", "archive_follows", "timeline", "thread", "posts", "posts_file"])
+))]
 struct Args {
     /// Bluesky username (without @)
-    #[arg(short, long)]
-    username: String,
+    #[arg(short, long, required_unless_present = "public")]
+    username: Option<String>,
 
     /// Directory to save archived images
     #[arg(short, long, default_value = "./archive")]
     output: PathBuf,
 
     /// Bluesky app password (not your main password!)
-    #[arg(short, long, env = "BLUESKY_APP_PASSWORD")]
-    password: String,
+    #[arg(
+        short,
+        long,
+        env = "BLUESKY_APP_PASSWORD",
+        required_unless_present_any = ["oauth", "public"]
+    )]
+    password: Option<String>,
+
+    /// Log in through the browser with OAuth instead of an app password
+    #[arg(long)]
+    oauth: bool,
+
+    /// Archive public data without logging in (requires --archive-user,
+    /// --archive-reposts, --feed, --list-feed, --list-members, --search,
+    /// --archive-follows, --thread, --post or --posts-file)
+    #[arg(long, requires = "source")]
+    public: bool,
 
     /// Maximum number of posts to fetch per run (0 = unlimited)
     #[arg(short, long, default_value = "100")]
@@ -35,6 +67,10 @@ struct Args {
     #[arg(long)]
     nsfw_only: bool,
 
+    /// Also archive link-card thumbnails and linked GIFs, with the link's URL, title and description
+    #[arg(long, env = "BLUESKY_ARCHIVE_EXTERNALS")]
+    archive_externals: bool,
+
     /// Delay between API requests in milliseconds (helps avoid rate limits)
     #[arg(short, long, default_value = "0")]
     delay: u64,
@@ -46,11 +82,192 @@ struct Args {
     /// Archive all image posts from a specific user (without @)
     #[arg(long)]
     archive_user: Option<String>,
+
+    /// Archive the image posts a specific user has reposted (without @)
+    #[arg(long)]
+    archive_reposts: Option<String>,
+
+    /// Archive image posts from a custom feed (at:// URI or bsky.app feed URL)
+    #[arg(long)]
+    feed: Option<String>,
+
+    /// Archive image posts from a list's feed (at:// URI or bsky.app list URL)
+    #[arg(long)]
+    list_feed: Option<String>,
+
+    /// Archive all image posts from each member of a list (at:// URI or bsky.app list URL)
+    #[arg(long)]
+    list_members: Option<String>,
+
+    /// Archive all image posts from every account this user follows (without @)
+    #[arg(long)]
+    archive_follows: Option<String>,
+
+    /// Number of accounts archived at once with --archive-follows
+    #[arg(long, env = "BLUESKY_CONCURRENCY", default_value = "1")]
+    concurrency: usize,
+
+    /// Archive image posts from your home timeline, reposts included
+    #[arg(long, conflicts_with = "public")]
+    timeline: bool,
+
+    /// Archive a single post (at:// URI or bsky.app post URL; repeatable)
+    #[arg(long = "post")]
+    posts: Vec<String>,
+
+    /// Archive the posts listed in a file, one URL or at:// URI per line ("-" for stdin)
+    #[arg(long)]
+    posts_file: Option<PathBuf>,
+
+    /// Archive the images of every post in a thread (at:// URI or bsky.app post URL)
+    #[arg(long)]
+    thread: Option<String>,
+
+    /// Levels of replies to fetch below the post with --thread
+    #[arg(long, default_value = "6")]
+    thread_depth: u32,
+
+    /// Number of parent posts to fetch above the post with --thread
+    #[arg(long, default_value = "80")]
+    parent_height: u32,
+
+    /// Archive image posts matching a search query (e.g. "#hashtag" or "cat art")
+    #[arg(long)]
+    search: Option<String>,
+
+    /// Only search posts by this user (handle or DID)
+    #[arg(long, requires = "search")]
+    search_author: Option<String>,
+
+    /// Only search posts in this language (e.g. en)
+    #[arg(long, requires = "search")]
+    search_lang: Option<String>,
+
+    /// Only search posts made on or after this date (YYYY-MM-DD or RFC 3339 datetime)
+    #[arg(long, requires = "search")]
+    since: Option<String>,
+
+    /// Only search posts made before this date (YYYY-MM-DD or RFC 3339 datetime)
+    #[arg(long, requires = "search")]
+    until: Option<String>,
+
+    /// Order of search results
+    #[arg(long, requires = "search", value_parser = ["top", "latest"])]
+    sort: Option<String>,
+
+    /// Only search posts with this hashtag (repeatable)
+    #[arg(long = "tag", requires = "search")]
+    tags: Vec<String>,
+
+    /// AppView URL used for feed reads (default: https://bsky.social, or
+    /// https://public.api.bsky.app with --public)
+    #[arg(long, env = "BLUESKY_APPVIEW_URL")]
+    appview_url: Option<String>,
+
+    /// PDS URL used for authentication (createSession)
+    #[arg(long, env = "BLUESKY_PDS_URL", default_value = bluesky::DEFAULT_SERVICE_URL)]
+    pds_url: String,
+
+    /// Host used to download blobs (getBlob)
+    #[arg(long, env = "BLUESKY_BLOB_URL", default_value = bluesky::DEFAULT_SERVICE_URL)]
+    blob_url: String,
+
+    /// PLC directory used to resolve authors' did:plc documents
+    #[arg(long, env = "BLUESKY_PLC_DIRECTORY", default_value = identity::DEFAULT_PLC_DIRECTORY)]
+    plc_directory: String,
+
+    /// Video service used to fetch HLS playlists when a video blob can't be downloaded
+    #[arg(long, env = "BLUESKY_VIDEO_URL", default_value = bluesky::VIDEO_SERVICE_URL)]
+    video_url: String,
+
+    /// File used to save the session between runs (default: <output>/.session.json)
+    #[arg(long, env = "BLUESKY_SESSION_FILE")]
+    session_file: Option<PathBuf>,
+
+    /// Always log in with the password instead of reusing a saved session
+    #[arg(long)]
+    no_session_cache: bool,
+
+    /// Sign-in code emailed to accounts with two-factor authentication enabled
+    #[arg(long, env = "BLUESKY_AUTH_FACTOR_TOKEN")]
+    auth_factor_token: Option<String>,
+}
+
+impl Args {
+    fn username(&self) -> Result<&str> {
+        self.username
+            .as_deref()
+            .ok_or_else(|| anyhow!("--username is required unless --public is set"))
+    }
+
+    /// The search to archive, if any.
+    fn search_query(&self) -> Option<SearchQuery> {
+        let query = self.search.as_ref()?;
+        Some(SearchQuery {
+            author: self.search_author.clone(),
+            lang: self.search_lang.clone(),
+            since: self.since.clone(),
+            until: self.until.clone(),
+            sort: self.sort.clone(),
+            tags: self.tags.clone(),
+            ..SearchQuery::new(query)
+        })
+    }
+}
+
+async fn login(client: &mut bluesky::Client, args: &Args) -> Result<()> {
+    let username = args.username()?;
+    if args.oauth {
+        let open_url = |url: &str| {
+            println!(
+                "Open this URL in your browser to authorize the archiver:\n\n  {}\n",
+                url
+            );
+        };
+        return if args.no_session_cache {
+            client.login_oauth(username, open_url).await
+        } else {
+            client.resume_or_login_oauth(username, open_url).await
+        };
+    }
+
+    // clap enforces the password whenever neither --oauth nor --public is given
+    let password = args.password.as_deref().unwrap_or_default();
+    let token = args.auth_factor_token.as_deref();
+    let result = if args.no_session_cache {
+        client
+            .login_with_auth_factor(username, password, token)
+            .await
+    } else {
+        client.resume_or_login(username, password, token).await
+    };
+
+    match result {
+        Err(e) if e.is::<bluesky::AuthFactorTokenRequired>() && token.is_none() => {
+            // Only prompt when someone is there to answer; cron runs should use the flag
+            if !std::io::stdin().is_terminal() {
+                return Err(e.context(
+                    "Pass the emailed code with --auth-factor-token or BLUESKY_AUTH_FACTOR_TOKEN",
+                ));
+            }
+
+            print!("Enter the sign-in code sent to your email: ");
+            std::io::stdout().flush()?;
+            let mut code = String::new();
+            std::io::stdin().read_line(&mut code)?;
+
+            client
+                .login_with_auth_factor(username, password, Some(code.trim()))
+                .await
+        }
+        result => result,
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    let search = args.search_query();
 
     // Set up logging
     let log_level = if args.verbose { "debug" } else { "info" };
@@ -59,7 +276,11 @@ async fn main() -> Result<()> {
         .with_env_filter(EnvFilter::new(log_level))
         .init();
 
-    info!("Starting Bluesky archiver for user: {}", args.username);
+    if args.public {
+        info!("Starting Bluesky archiver in public mode (not logged in)");
+    } else {
+        info!("Starting Bluesky archiver for user: {}", args.username()?);
+    }
 
     // Create output directory if it doesn't exist
     std::fs::create_dir_all(&args.output)?;
@@ -69,105 +290,375 @@ async fn main() -> Result<()> {
     let db = database::Database::new(&db_path)?;
 
     // Create Bluesky client and authenticate
-    let mut client = bluesky::Client::new();
-    client.login(&args.username, &args.password).await?;
-
-    // Check if we're archiving a specific user's posts or liked posts
-    if let Some(target_user) = args.archive_user {
-        info!("Archiving all image posts from user: {}", target_user);
-
-        // Fetch user's posts
-        let cursor_file = args.output.join(format!(".cursor_{}", target_user));
-        let start_cursor = if args.resume && cursor_file.exists() {
-            match std::fs::read_to_string(&cursor_file) {
-                Ok(cursor) => {
-                    info!("Resuming from saved cursor");
-                    Some(cursor.trim().to_string())
-                }
-                Err(e) => {
-                    warn!("Failed to read cursor file: {}", e);
-                    None
-                }
-            }
-        } else {
-            None
-        };
+    let default_appview = if args.public {
+        bluesky::PUBLIC_APPVIEW_URL
+    } else {
+        bluesky::DEFAULT_SERVICE_URL
+    };
+    let mut client = bluesky::Client::with_endpoints(bluesky::Endpoints {
+        appview: args
+            .appview_url
+            .clone()
+            .unwrap_or_else(|| default_appview.to_string()),
+        pds: args.pds_url.clone(),
+        blob_host: args.blob_url.clone(),
+        plc_directory: args.plc_directory.clone(),
+        video: args.video_url.clone(),
+    });
+    if !args.no_session_cache {
+        let session_file = args
+            .session_file
+            .clone()
+            .unwrap_or_else(|| args.output.join(".session.json"));
+        client.set_session_store(session::SessionStore::new(session_file));
+    }
+    if !args.public {
+        login(&mut client, &args).await?;
+    }
 
-        let cursor_file_clone = cursor_file.clone();
-        let posts = client
-            .get_user_posts_with_options(
-                &target_user,
-                args.limit,
-                args.delay,
-                start_cursor,
-                Some(Box::new(move |cursor| {
-                    if let Err(e) = std::fs::write(&cursor_file_clone, cursor) {
-                        warn!("Failed to save cursor: {}", e);
-                    }
-                })),
-            )
-            .await?;
+    let mut archiver = Archiver::new(db, args.output.clone(), &client);
+    archiver.set_archive_externals(args.archive_externals);
+
+    // Check if we're archiving a specific user's posts, a feed, a list or liked posts
+    let stats = if let Some(target_user) = &args.archive_user {
+        archive_user(&client, &archiver, &args, target_user).await?
+    } else if let Some(target_user) = &args.archive_reposts {
+        info!("Archiving image posts reposted by: {}", target_user);
 
-        // Clear cursor on successful completion
-        if cursor_file.exists() {
-            let _ = std::fs::remove_file(&cursor_file);
+        let cursor_file = args.output.join(format!(".cursor_reposts_{}", target_user));
+        let pages = client.reposts_pages(
+            target_user,
+            paginate_options(&args, read_cursor(&args, &cursor_file)),
+        );
+        archive_source(&archiver, &args, &cursor_file, pages).await?
+    } else if let Some(feed) = &args.feed {
+        let feed = client.resolve_feed_uri(feed).await?;
+        info!("Archiving image posts from feed: {}", feed);
+
+        let cursor_file = args
+            .output
+            .join(format!(".cursor_feed_{}", feed.file_key()));
+        let pages = client.feed_pages(
+            &feed,
+            paginate_options(&args, read_cursor(&args, &cursor_file)),
+        );
+        archive_source(&archiver, &args, &cursor_file, pages).await?
+    } else if let Some(list) = &args.list_feed {
+        let list = client.resolve_list_uri(list).await?;
+        info!("Archiving image posts from list feed: {}", list);
+
+        let cursor_file = args
+            .output
+            .join(format!(".cursor_list_{}", list.file_key()));
+        let pages = client.list_feed_pages(
+            &list,
+            paginate_options(&args, read_cursor(&args, &cursor_file)),
+        );
+        archive_source(&archiver, &args, &cursor_file, pages).await?
+    } else if let Some(query) = search {
+        info!("Archiving image posts matching search: {}", query.query);
+
+        let cursor_file = args
+            .output
+            .join(format!(".cursor_search_{}", query.file_key()));
+        let pages = client.search_pages(
+            &query,
+            paginate_options(&args, read_cursor(&args, &cursor_file)),
+        );
+        archive_source(&archiver, &args, &cursor_file, pages).await?
+    } else if let Some(actor) = &args.archive_follows {
+        let cursors = Arc::new(Mutex::new(database::Database::new(&db_path)?));
+        if args.concurrency > 1 {
+            archiver.set_progress(MultiProgress::new());
         }
+        archive_follows(&client, &archiver, &args, &cursors, actor).await?
+    } else if let Some(post) = &args.thread {
+        let post = client.resolve_post_uri(post).await?;
+        info!("Archiving thread of {}", post);
 
-        // Archive images from user's posts
-        let archiver = archive::Archiver::new(db, args.output, &client);
-        let stats = archiver.archive_posts(posts, args.nsfw_only).await?;
+        let thread = client
+            .get_post_thread(&post, args.thread_depth, args.parent_height)
+            .await?;
+        archiver.archive_thread(thread, args.nsfw_only).await?
+    } else if !args.posts.is_empty() || args.posts_file.is_some() {
+        let posts = match &args.posts_file {
+            Some(path) => read_post_list(path)?,
+            None => args.posts.clone(),
+        };
+        info!("Archiving {} posts", posts.len());
+
+        let uris = client.resolve_post_uris(&posts).await?;
+        let posts = client.get_posts(&uris).await?;
+        archiver.archive_posts(posts, args.nsfw_only).await?
+    } else if args.timeline {
+        info!("Archiving image posts from your home timeline");
+        archiver.set_source("timeline");
 
+        let cursor_file = args.output.join(".cursor_timeline");
+        let pages =
+            client.timeline_pages(paginate_options(&args, read_cursor(&args, &cursor_file)));
+        archive_source(&archiver, &args, &cursor_file, pages).await?
+    } else if let Some(list) = &args.list_members {
+        let list = client.resolve_list_uri(list).await?;
+        let members = client.list_members(&list, args.delay).await?;
         info!(
-            "Archive complete. Downloaded: {}, Skipped: {}, Failed: {}",
-            stats.downloaded, stats.skipped, stats.failed
+            "Archiving image posts from {} members of list {}",
+            members.len(),
+            list
         );
-    } else {
-        // Original behavior: fetch liked posts
-        let cursor_file = args.output.join(".cursor");
-        let start_cursor = if args.resume && cursor_file.exists() {
-            match std::fs::read_to_string(&cursor_file) {
-                Ok(cursor) => {
-                    info!("Resuming from saved cursor");
-                    Some(cursor.trim().to_string())
-                }
+
+        let mut stats = ArchiveStats::default();
+        for member in &members {
+            // One unavailable account shouldn't stop the rest of the list
+            match archive_member(&client, &archiver, &args, member).await {
+                Ok(member_stats) => stats.merge(member_stats),
                 Err(e) => {
-                    warn!("Failed to read cursor file: {}", e);
-                    None
+                    warn!("Failed to archive {}: {}", member.handle, e);
+                    stats.failed += 1;
                 }
             }
-        } else {
-            None
-        };
+        }
+        stats
+    } else {
+        // Original behavior: fetch liked posts
+        let cursor_file = args.output.join(".cursor");
+        let pages = client.likes_pages(
+            args.username()?,
+            paginate_options(&args, read_cursor(&args, &cursor_file)),
+        );
+        archive_source(&archiver, &args, &cursor_file, pages).await?
+    };
 
-        let cursor_file_clone = cursor_file.clone();
-        let likes = client
-            .get_likes_with_options(
-                &args.username,
-                args.limit,
-                args.delay,
-                start_cursor,
-                Some(Box::new(move |cursor| {
-                    if let Err(e) = std::fs::write(&cursor_file_clone, cursor) {
-                        warn!("Failed to save cursor: {}", e);
-                    }
-                })),
-            )
-            .await?;
+    info!(
+        "Archive complete. Downloaded: {}, Skipped: {}, Failed: {}",
+        stats.downloaded, stats.skipped, stats.failed
+    );
+
+    Ok(())
+}
+
+/// Archives all image posts from `target_user`, resuming from their own cursor.
+async fn archive_user(
+    client: &bluesky::Client,
+    archiver: &Archiver<'_>,
+    args: &Args,
+    target_user: &str,
+) -> Result<ArchiveStats> {
+    info!("Archiving all image posts from user: {}", target_user);
+
+    let cursor_file = args.output.join(format!(".cursor_{}", target_user));
+    let pages = client.user_posts_pages(
+        target_user,
+        paginate_options(args, read_cursor(args, &cursor_file)),
+    );
+    archive_source(archiver, args, &cursor_file, pages).await
+}
+
+/// Archives all image posts from a list member. The member is looked up and
+/// keyed by DID, so an unverified handle or a handle change doesn't break it.
+async fn archive_member(
+    client: &bluesky::Client,
+    archiver: &Archiver<'_>,
+    args: &Args,
+    member: &Author,
+) -> Result<ArchiveStats> {
+    info!(
+        "Archiving all image posts from list member: {}",
+        member.handle
+    );
+
+    let cursor_file = args
+        .output
+        .join(format!(".cursor_{}", member.did.replace(':', "_")));
+    let pages = client.user_posts_pages(
+        &member.did,
+        paginate_options(args, read_cursor(args, &cursor_file)),
+    );
+    archive_source(archiver, args, &cursor_file, pages).await
+}
+
+/// Archives all image posts from every account `actor` follows, up to
+/// `--concurrency` accounts at a time, then logs what was archived per account.
+async fn archive_follows(
+    client: &bluesky::Client,
+    archiver: &Archiver<'_>,
+    args: &Args,
+    cursors: &Arc<Mutex<database::Database>>,
+    actor: &str,
+) -> Result<ArchiveStats> {
+    let follows = client.follows(actor, args.delay).await?;
+    info!(
+        "Archiving image posts from {} accounts followed by {}",
+        follows.len(),
+        actor
+    );
 
-        // Clear cursor on successful completion
-        if cursor_file.exists() {
-            let _ = std::fs::remove_file(&cursor_file);
+    let mut results: Vec<_> = stream::iter(&follows)
+        .map(|account| async move {
+            let result = archive_followed(client, archiver, args, cursors, account).await;
+            (account, result)
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect()
+        .await;
+    results.sort_by(|(a, _), (b, _)| a.handle.cmp(&b.handle));
+
+    info!("Per-account summary:");
+    let mut stats = ArchiveStats::default();
+    for (account, result) in results {
+        match result {
+            Ok(account_stats) => {
+                info!(
+                    "  {}: Downloaded: {}, Skipped: {}, Failed: {}",
+                    account.handle,
+                    account_stats.downloaded,
+                    account_stats.skipped,
+                    account_stats.failed
+                );
+                stats.merge(account_stats);
+            }
+            Err(e) => {
+                // One unavailable account shouldn't stop the rest
+                warn!("  {}: {}", account.handle, e);
+                stats.failed += 1;
+            }
         }
+    }
 
-        // Archive images from liked posts
-        let archiver = archive::Archiver::new(db, args.output, &client);
-        let stats = archiver.archive_posts(likes, args.nsfw_only).await?;
+    Ok(stats)
+}
 
-        info!(
-            "Archive complete. Downloaded: {}, Skipped: {}, Failed: {}",
-            stats.downloaded, stats.skipped, stats.failed
+/// Archives one followed account, keeping its resume cursor in the database.
+async fn archive_followed(
+    client: &bluesky::Client,
+    archiver: &Archiver<'_>,
+    args: &Args,
+    cursors: &Arc<Mutex<database::Database>>,
+    account: &Author,
+) -> Result<ArchiveStats> {
+    let start_cursor = if args.resume {
+        cursors.lock().unwrap().get_account_cursor(&account.did)?
+    } else {
+        None
+    };
+    let pages = client.user_posts_pages(&account.did, paginate_options(args, start_cursor));
+
+    let checkpoint: CursorCallback = {
+        let cursors = Arc::clone(cursors);
+        let (did, handle) = (account.did.clone(), account.handle.clone());
+        Box::new(move |cursor| {
+            let saved = cursors
+                .lock()
+                .unwrap()
+                .save_account_cursor(&did, &handle, cursor);
+            if let Err(e) = saved {
+                warn!("Failed to save cursor for {}: {}", handle, e);
+            }
+        })
+    };
+    let stats = archiver
+        .archive_pages(pages, args.nsfw_only, Some(checkpoint))
+        .await?;
+
+    // As with cursor files, keep the cursor after a failure so --resume retries it
+    if stats.retryable == 0 {
+        cursors.lock().unwrap().clear_account_cursor(&account.did)?;
+    }
+
+    Ok(stats)
+}
+
+/// Archives every page of a source, checkpointing to `cursor_file`.
+async fn archive_source<S, T>(
+    archiver: &Archiver<'_>,
+    args: &Args,
+    cursor_file: &Path,
+    pages: S,
+) -> Result<ArchiveStats>
+where
+    S: Stream<Item = Result<FeedPage<T>>>,
+    T: ArchiveItem,
+{
+    let stats = archiver
+        .archive_pages(pages, args.nsfw_only, Some(save_cursor(cursor_file)))
+        .await?;
+
+    // Clear cursor on successful completion. After a failure a retry could fix
+    // it is kept at the last fully archived page so --resume retries the
+    // failed posts; other failures are recorded in failed_blobs instead.
+    if stats.failed > stats.retryable {
+        warn!(
+            "{} posts failed in ways a retry won't fix; resuming moves past them",
+            stats.failed - stats.retryable
+        );
+    }
+    if stats.retryable > 0 {
+        warn!(
+            "{} posts failed to archive; run again with --resume to retry them",
+            stats.retryable
         );
+    } else if cursor_file.exists() {
+        let _ = std::fs::remove_file(cursor_file);
     }
 
-    Ok(())
+    Ok(stats)
+}
+
+/// Reads post URLs or at:// URIs, one per line, from `path` or stdin for "-".
+/// Blank lines and lines starting with `#` are skipped.
+fn read_post_list(path: &Path) -> Result<Vec<String>> {
+    let text = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?
+    };
+
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Reads the cursor saved in `cursor_file` when resuming.
+fn read_cursor(args: &Args, cursor_file: &Path) -> Option<String> {
+    if args.resume && cursor_file.exists() {
+        match std::fs::read_to_string(cursor_file) {
+            Ok(cursor) => {
+                info!("Resuming from saved cursor");
+                Some(cursor.trim().to_string())
+            }
+            Err(e) => {
+                warn!("Failed to read cursor file: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    }
+}
+
+/// Builds paging options that resume from `start_cursor`.
+fn paginate_options(args: &Args, start_cursor: Option<String>) -> PaginateOptions {
+    PaginateOptions {
+        limit: args.limit,
+        delay_ms: args.delay,
+        start_cursor,
+        // The cursor is saved by the archiver once a page is archived, not when it is fetched
+        cursor_callback: None,
+        // The archiver shows its own progress while pages stream in
+        progress: false,
+    }
+}
+
+/// Saves resume checkpoints to `cursor_file`.
+fn save_cursor(cursor_file: &Path) -> CursorCallback {
+    let cursor_file = cursor_file.to_path_buf();
+    Box::new(move |cursor| {
+        if let Err(e) = std::fs::write(&cursor_file, cursor) {
+            warn!("Failed to save cursor: {}", e);
+        }
+    })
 }
@@ -0,0 +1,522 @@
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::RngCore;
+use reqwest::{Client as HttpClient, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{debug, info};
+
+use crate::session::SessionRejected;
+
+/// Scopes requested for archiving: account identity plus the app-password-equivalent grant.
+pub const OAUTH_SCOPE: &str = "atproto transition:generic";
+
+/// How long to wait for the browser to hit the loopback redirect.
+const CALLBACK_TIMEOUT_SECS: u64 = 300;
+
+/// DPoP-bound tokens from an atproto OAuth authorization server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthSession {
+    pub did: String,
+    /// PDS the tokens are valid for; all authenticated XRPC calls go here
+    pub pds: String,
+    pub issuer: String,
+    pub token_endpoint: String,
+    pub client_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Base64url-encoded P-256 private key the tokens are bound to
+    pub dpop_key: String,
+    #[serde(default)]
+    pub auth_server_nonce: Option<String>,
+    #[serde(default)]
+    pub resource_nonce: Option<String>,
+}
+
+/// Key pair used to sign DPoP proofs.
+pub struct DpopKey(SigningKey);
+
+impl DpopKey {
+    pub fn generate() -> Self {
+        Self(SigningKey::random(&mut rand::rngs::OsRng))
+    }
+
+    pub fn from_encoded(encoded: &str) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(encoded)?;
+        let key = SigningKey::from_slice(&bytes).map_err(|e| anyhow!("Invalid DPoP key: {}", e))?;
+        Ok(Self(key))
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.0.to_bytes())
+    }
+
+    pub fn public_jwk(&self) -> serde_json::Value {
+        let point = self.0.verifying_key().to_encoded_point(false);
+        json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(point.x().expect("uncompressed point")),
+            "y": URL_SAFE_NO_PAD.encode(point.y().expect("uncompressed point")),
+        })
+    }
+
+    pub fn verifying_key(&self) -> &p256::ecdsa::VerifyingKey {
+        self.0.verifying_key()
+    }
+
+    /// Signs a DPoP proof JWT for one request.
+    ///
+    /// `access_token` is set for requests to the resource server, where the
+    /// proof must carry a hash of the token it accompanies.
+    pub fn proof(
+        &self,
+        method: &str,
+        url: &str,
+        nonce: Option<&str>,
+        access_token: Option<&str>,
+    ) -> Result<String> {
+        // htu is the target URI without query and fragment
+        let mut htu = Url::parse(url)?;
+        htu.set_query(None);
+        htu.set_fragment(None);
+
+        let header = json!({
+            "typ": "dpop+jwt",
+            "alg": "ES256",
+            "jwk": self.public_jwk(),
+        });
+        let mut claims = json!({
+            "jti": random_token(16),
+            "htm": method,
+            "htu": htu.as_str(),
+            "iat": Utc::now().timestamp(),
+        });
+        if let Some(nonce) = nonce {
+            claims["nonce"] = json!(nonce);
+        }
+        if let Some(token) = access_token {
+            claims["ath"] = json!(URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes())));
+        }
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature: Signature = self.0.sign(signing_input.as_bytes());
+
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+}
+
+/// S256 PKCE challenge for a code verifier.
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+fn random_token(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::rngs::OsRng.fill_bytes(&mut buf);
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// Client id for a loopback (native) client, as defined by the atproto OAuth profile.
+pub fn loopback_client_id(redirect_uri: &str) -> String {
+    Url::parse_with_params(
+        "http://localhost",
+        &[("redirect_uri", redirect_uri), ("scope", OAUTH_SCOPE)],
+    )
+    .expect("static base URL")
+    .to_string()
+}
+
+/// Returns true when a response asks the client to retry with a fresh DPoP nonce.
+pub fn is_use_dpop_nonce(status: StatusCode, www_authenticate: Option<&str>, body: &[u8]) -> bool {
+    if status != StatusCode::BAD_REQUEST && status != StatusCode::UNAUTHORIZED {
+        return false;
+    }
+    if www_authenticate.is_some_and(|h| h.contains("use_dpop_nonce")) {
+        return true;
+    }
+    serde_json::from_slice::<OAuthError>(body)
+        .map(|e| e.error == "use_dpop_nonce")
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Deserialize)]
+struct OAuthError {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// An error response from the authorization server.
+#[derive(Debug)]
+struct ServerError {
+    status: StatusCode,
+    message: String,
+}
+
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+#[derive(Debug, Deserialize)]
+struct ProtectedResourceMetadata {
+    authorization_servers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthServerMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub pushed_authorization_request_endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParResponse {
+    request_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+    sub: String,
+}
+
+/// Finds the authorization server protecting `pds` and fetches its metadata.
+pub async fn discover(http: &HttpClient, pds: &str) -> Result<AuthServerMetadata> {
+    let resource_url = format!(
+        "{}/.well-known/oauth-protected-resource",
+        pds.trim_end_matches('/')
+    );
+    let resource: ProtectedResourceMetadata = http
+        .get(&resource_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let issuer = resource
+        .authorization_servers
+        .first()
+        .ok_or_else(|| anyhow!("{} lists no authorization servers", pds))?
+        .trim_end_matches('/')
+        .to_string();
+
+    let metadata: AuthServerMetadata = http
+        .get(format!("{}/.well-known/oauth-authorization-server", issuer))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    if metadata.issuer.trim_end_matches('/') != issuer {
+        return Err(anyhow!(
+            "Authorization server issuer {} does not match {}",
+            metadata.issuer,
+            issuer
+        ));
+    }
+
+    Ok(metadata)
+}
+
+/// Posts a form to the authorization server with a DPoP proof, retrying once
+/// if the server hands out a new nonce.
+async fn post_with_dpop(
+    http: &HttpClient,
+    key: &DpopKey,
+    url: &str,
+    form: &[(&str, &str)],
+    nonce: &mut Option<String>,
+) -> Result<Response> {
+    for _ in 0..2 {
+        let proof = key.proof("POST", url, nonce.as_deref(), None)?;
+        let response = http
+            .post(url)
+            .header("DPoP", proof)
+            .form(form)
+            .send()
+            .await?;
+
+        if let Some(new_nonce) = response
+            .headers()
+            .get("dpop-nonce")
+            .and_then(|v| v.to_str().ok())
+        {
+            *nonce = Some(new_nonce.to_string());
+        }
+
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let body = response.bytes().await?;
+        if is_use_dpop_nonce(status, None, &body) {
+            debug!("Authorization server requested a DPoP nonce, retrying");
+            continue;
+        }
+
+        let error = match serde_json::from_slice::<OAuthError>(&body) {
+            Ok(e) => format!("{}: {}", e.error, e.error_description.unwrap_or_default()),
+            Err(_) => String::from_utf8_lossy(&body).to_string(),
+        };
+        return Err(ServerError {
+            status,
+            message: format!("OAuth request to {} failed: {} - {}", url, status, error),
+        }
+        .into());
+    }
+
+    Err(anyhow!(
+        "OAuth request to {} kept asking for a new DPoP nonce",
+        url
+    ))
+}
+
+/// Runs the interactive authorization code flow for `did`.
+///
+/// `open_url` is called with the authorization URL the user must visit; the
+/// browser is then redirected to a loopback listener to finish the exchange.
+pub async fn authorize<F>(
+    http: &HttpClient,
+    did: &str,
+    pds: &str,
+    login_hint: &str,
+    open_url: F,
+) -> Result<OAuthSession>
+where
+    F: FnOnce(&str),
+{
+    let metadata = discover(http, pds).await?;
+    debug!("Using authorization server {}", metadata.issuer);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let redirect_uri = format!(
+        "http://127.0.0.1:{}/callback",
+        listener.local_addr()?.port()
+    );
+    let client_id = loopback_client_id(&redirect_uri);
+
+    let key = DpopKey::generate();
+    let verifier = random_token(32);
+    let challenge = pkce_challenge(&verifier);
+    let state = random_token(16);
+    let mut nonce = None;
+
+    let par: ParResponse = post_with_dpop(
+        http,
+        &key,
+        &metadata.pushed_authorization_request_endpoint,
+        &[
+            ("client_id", client_id.as_str()),
+            ("response_type", "code"),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            ("state", state.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("scope", OAUTH_SCOPE),
+            ("login_hint", login_hint),
+        ],
+        &mut nonce,
+    )
+    .await?
+    .json()
+    .await?;
+
+    let authorize_url = Url::parse_with_params(
+        &metadata.authorization_endpoint,
+        &[
+            ("client_id", client_id.as_str()),
+            ("request_uri", par.request_uri.as_str()),
+        ],
+    )?;
+    open_url(authorize_url.as_str());
+
+    let params = tokio::time::timeout(
+        std::time::Duration::from_secs(CALLBACK_TIMEOUT_SECS),
+        wait_for_callback(&listener),
+    )
+    .await
+    .map_err(|_| anyhow!("Timed out waiting for the OAuth redirect"))??;
+
+    if let Some(error) = params.get("error") {
+        return Err(anyhow!(
+            "Authorization failed: {} {}",
+            error,
+            params
+                .get("error_description")
+                .map(String::as_str)
+                .unwrap_or("")
+        ));
+    }
+    if params.get("state") != Some(&state) {
+        return Err(anyhow!("OAuth state mismatch"));
+    }
+    if let Some(iss) = params.get("iss") {
+        if iss.trim_end_matches('/') != metadata.issuer.trim_end_matches('/') {
+            return Err(anyhow!("OAuth issuer mismatch: {}", iss));
+        }
+    }
+    let code = params
+        .get("code")
+        .ok_or_else(|| anyhow!("OAuth redirect did not include a code"))?;
+
+    let token: TokenResponse = post_with_dpop(
+        http,
+        &key,
+        &metadata.token_endpoint,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", verifier.as_str()),
+            ("client_id", client_id.as_str()),
+        ],
+        &mut nonce,
+    )
+    .await?
+    .json()
+    .await?;
+
+    if token.sub != did {
+        return Err(anyhow!(
+            "Token was issued for {} instead of {}",
+            token.sub,
+            did
+        ));
+    }
+
+    info!("Authorized via OAuth as DID: {}", did);
+    session_from_token(
+        token,
+        OAuthSession {
+            did: did.to_string(),
+            pds: pds.trim_end_matches('/').to_string(),
+            issuer: metadata.issuer,
+            token_endpoint: metadata.token_endpoint,
+            client_id,
+            access_token: String::new(),
+            refresh_token: String::new(),
+            expires_at: None,
+            dpop_key: key.encode(),
+            auth_server_nonce: nonce,
+            resource_nonce: None,
+        },
+    )
+}
+
+/// Exchanges the refresh token for a new token pair.
+pub async fn refresh(http: &HttpClient, session: &OAuthSession) -> Result<OAuthSession> {
+    let key = DpopKey::from_encoded(&session.dpop_key)?;
+    let mut nonce = session.auth_server_nonce.clone();
+
+    let token: TokenResponse = post_with_dpop(
+        http,
+        &key,
+        &session.token_endpoint,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", session.refresh_token.as_str()),
+            ("client_id", session.client_id.as_str()),
+        ],
+        &mut nonce,
+    )
+    .await
+    .map_err(|e| match e.downcast::<ServerError>() {
+        // e.g. invalid_grant for a revoked or expired refresh token
+        Ok(error) if error.status.is_client_error() => SessionRejected(error.message).into(),
+        Ok(error) => error.into(),
+        Err(e) => e,
+    })?
+    .json()
+    .await?;
+
+    if token.sub != session.did {
+        return Err(anyhow!("Refreshed token belongs to {}", token.sub));
+    }
+
+    session_from_token(
+        token,
+        OAuthSession {
+            auth_server_nonce: nonce,
+            ..session.clone()
+        },
+    )
+}
+
+fn session_from_token(token: TokenResponse, mut session: OAuthSession) -> Result<OAuthSession> {
+    if !token.token_type.eq_ignore_ascii_case("DPoP") {
+        return Err(anyhow!("Unexpected token type {}", token.token_type));
+    }
+
+    session.access_token = token.access_token;
+    // Servers may keep the old refresh token instead of rotating it
+    if let Some(refresh_token) = token.refresh_token {
+        session.refresh_token = refresh_token;
+    }
+    session.expires_at = token
+        .expires_in
+        .map(|seconds| Utc::now() + Duration::seconds(seconds));
+    Ok(session)
+}
+
+async fn wait_for_callback(listener: &TcpListener) -> Result<HashMap<String, String>> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut buf = vec![0u8; 8192];
+        let n = stream.read(&mut buf).await?;
+        let request = String::from_utf8_lossy(&buf[..n]);
+        let path = request.split_whitespace().nth(1).unwrap_or("/");
+        let url = Url::parse(&format!("http://127.0.0.1{}", path))?;
+
+        // Browsers also ask for things like /favicon.ico
+        if url.path() != "/callback" {
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+            continue;
+        }
+
+        let body = "Login complete. You can close this window and return to the archiver.";
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await?;
+
+        return Ok(url.query_pairs().into_owned().collect());
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, Result};
+use futures::stream::{self, Stream, TryStreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use tokio::time::{sleep, Duration};
+use tracing::{info, warn};
+
+use crate::bluesky::Client;
+
+pub type CursorCallback = Box<dyn Fn(&str) + Send>;
+
+/// Largest page size the app.bsky list endpoints accept.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// A response from a cursor-paginated XRPC list endpoint.
+pub trait Page: DeserializeOwned {
+    type Item;
+
+    fn cursor(&self) -> Option<&str>;
+    fn into_items(self) -> Vec<Self::Item>;
+}
+
+/// One page of results after filtering.
+#[derive(Debug)]
+pub struct FeedPage<T> {
+    pub items: Vec<T>,
+    /// Cursor for the page after this one; `None` on the last page, including
+    /// a page cut short by the limit
+    pub cursor: Option<String>,
+}
+
+/// Paging behaviour shared by every list source.
+pub struct PaginateOptions {
+    /// Maximum number of items to yield (0 = unlimited)
+    pub limit: usize,
+    /// Delay between page requests in milliseconds
+    pub delay_ms: u64,
+    /// Cursor to resume from
+    pub start_cursor: Option<String>,
+    /// Called with the next cursor after each page is fetched
+    pub cursor_callback: Option<CursorCallback>,
+    /// Show a progress bar while fetching
+    pub progress: bool,
+}
+
+impl Default for PaginateOptions {
+    fn default() -> Self {
+        Self {
+            limit: 0,
+            delay_ms: 0,
+            start_cursor: None,
+            cursor_callback: None,
+            progress: true,
+        }
+    }
+}
+
+/// Describes the list endpoint being paged.
+pub struct ListRequest {
+    /// Full XRPC URL of the endpoint
+    pub url: String,
+    /// Query parameters other than `limit` and `cursor`
+    pub params: Vec<(&'static str, String)>,
+    /// What is being fetched, for progress and error messages ("likes", "user posts")
+    pub description: &'static str,
+    /// Fail with "Not authenticated" instead of sending anonymous requests
+    pub require_auth: bool,
+}
+
+struct PageState<'a, P, T, F> {
+    client: &'a Client,
+    request: ListRequest,
+    options: PaginateOptions,
+    filter: F,
+    cursor: Option<String>,
+    fetched: usize,
+    pages: usize,
+    done: bool,
+    pb: ProgressBar,
+    _page: PhantomData<fn() -> (P, T)>,
+}
+
+impl Client {
+    /// Pages through a cursor-paginated list endpoint.
+    ///
+    /// `filter` maps each raw item to the value yielded, or drops it by
+    /// returning `None`; the limit counts yielded items only. Rate-limited
+    /// requests are retried with backoff, and the stream ends when the server
+    /// stops returning items or a cursor.
+    pub fn paginate<'a, P, T, F>(
+        &'a self,
+        request: ListRequest,
+        options: PaginateOptions,
+        filter: F,
+    ) -> impl Stream<Item = Result<FeedPage<T>>> + 'a
+    where
+        P: Page + 'a,
+        T: 'a,
+        F: FnMut(P::Item) -> Option<T> + 'a,
+    {
+        let pb = progress_bar(&options, request.description);
+        if options.start_cursor.is_some() {
+            info!("Resuming from saved cursor position");
+        }
+
+        let state = PageState::<P, T, F> {
+            client: self,
+            cursor: options.start_cursor.clone(),
+            request,
+            options,
+            filter,
+            fetched: 0,
+            pages: 0,
+            done: false,
+            pb,
+            _page: PhantomData,
+        };
+
+        stream::try_unfold(state, next_page)
+    }
+}
+
+/// Flattens a stream of pages into a stream of their items.
+pub fn into_items<'a, T: 'a>(
+    pages: impl Stream<Item = Result<FeedPage<T>>> + 'a,
+) -> impl Stream<Item = Result<T>> + 'a {
+    pages
+        .map_ok(|page| stream::iter(page.items.into_iter().map(Ok)))
+        .try_flatten()
+}
+
+fn progress_bar(options: &PaginateOptions, description: &str) -> ProgressBar {
+    if !options.progress {
+        return ProgressBar::hidden();
+    }
+
+    let (pb, template) = if options.limit == 0 {
+        (
+            ProgressBar::new_spinner(),
+            "{spinner:.green} [{elapsed_precise}] {pos} posts fetched ({per_sec}) {msg}",
+        )
+    } else {
+        (
+            ProgressBar::new(options.limit as u64),
+            "{spinner:.green} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} posts ({per_sec}) {msg}",
+        )
+    };
+
+    if let Ok(style) = ProgressStyle::default_bar().template(template) {
+        pb.set_style(style.progress_chars("=>-"));
+    }
+    if options.limit == 0 {
+        pb.set_message(format!("Fetching all {}...", description));
+    }
+    pb
+}
+
+async fn next_page<'a, P, T, F>(
+    mut state: PageState<'a, P, T, F>,
+) -> Result<Option<(FeedPage<T>, PageState<'a, P, T, F>)>>
+where
+    P: Page,
+    F: FnMut(P::Item) -> Option<T>,
+{
+    if state.done {
+        return Ok(None);
+    }
+
+    if state.request.require_auth && !state.client.is_authenticated() {
+        return Err(anyhow!("Not authenticated"));
+    }
+
+    if state.options.delay_ms > 0 && state.pages > 0 {
+        sleep(Duration::from_millis(state.options.delay_ms)).await;
+    }
+
+    let page: P = match fetch_page(&state).await {
+        Ok(page) => page,
+        Err(e) => {
+            state.pb.finish_and_clear();
+            return Err(e);
+        }
+    };
+    state.pages += 1;
+
+    let next_cursor = page.cursor().map(|c| c.to_string());
+    let raw_items = page.into_items();
+
+    if raw_items.is_empty() {
+        info!(
+            "No more posts returned, reached end of {}",
+            state.request.description
+        );
+        state
+            .pb
+            .finish_with_message(format!("Fetched {} posts", state.fetched));
+        return Ok(None);
+    }
+
+    let limit = state.options.limit;
+    let mut items = Vec::new();
+    for raw in raw_items {
+        if let Some(item) = (state.filter)(raw) {
+            items.push(item);
+            state.fetched += 1;
+            state.pb.inc(1);
+
+            if limit > 0 && state.fetched >= limit {
+                state.done = true;
+                state.pb.finish_with_message("Fetching complete");
+                return Ok(Some((
+                    FeedPage {
+                        items,
+                        cursor: None,
+                    },
+                    state,
+                )));
+            }
+        }
+    }
+
+    if limit == 0 {
+        state
+            .pb
+            .set_message(format!("Fetched {} posts...", state.fetched));
+    }
+
+    match &next_cursor {
+        Some(cursor) => {
+            if let Some(callback) = &state.options.cursor_callback {
+                callback(cursor);
+            }
+        }
+        None => {
+            info!(
+                "No cursor returned, reached end of {}",
+                state.request.description
+            );
+            state.done = true;
+            state
+                .pb
+                .finish_with_message(format!("Fetched {} posts", state.fetched));
+        }
+    }
+    state.cursor = next_cursor.clone();
+
+    Ok(Some((
+        FeedPage {
+            items,
+            cursor: next_cursor,
+        },
+        state,
+    )))
+}
+
+async fn fetch_page<P, T, F>(state: &PageState<'_, P, T, F>) -> Result<P>
+where
+    P: Page,
+{
+    let limit = state.options.limit;
+    let page_size = if limit == 0 {
+        MAX_PAGE_SIZE
+    } else {
+        MAX_PAGE_SIZE.min(limit)
+    };
+
+    let mut params = state.request.params.clone();
+    params.push(("limit", page_size.to_string()));
+    if let Some(cursor) = &state.cursor {
+        params.push(("cursor", cursor.clone()));
+    }
+
+    let url = &state.request.url;
+    let response = state
+        .client
+        .send_maybe_authed(&format!("Fetching {}", state.request.description), |http| {
+            http.get(url).query(&params)
+        })
+        .await?;
+
+    let status = response.status();
+
+    // Still rate limited once the retry policy has given up
+    if status.as_u16() == 429 {
+        return Err(anyhow!(
+            "Rate limited after retrying. Try again later or use --delay flag"
+        ));
+    }
+
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(anyhow!(
+            "Failed to fetch {}: {} - {}",
+            state.request.description,
+            status,
+            error_text
+        ));
+    }
+
+    let response_text = response.text().await?;
+    match serde_json::from_str(&response_text) {
+        Ok(page) => Ok(page),
+        Err(e) => {
+            // Log the error and response for debugging
+            warn!(
+                "Failed to parse {} response: {}",
+                state.request.description, e
+            );
+            warn!("Response text: {}", response_text);
+            Err(anyhow!(
+                "Failed to parse {} response: {}",
+                state.request.description,
+                e
+            ))
+        }
+    }
+}
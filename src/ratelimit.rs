@@ -0,0 +1,220 @@
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration, Instant};
+use tracing::debug;
+
+/// Requests per window the AppView allows before it starts answering 429.
+const DEFAULT_APPVIEW_LIMIT: u32 = 3000;
+/// Requests per window assumed for getBlob on each host until it says otherwise.
+const DEFAULT_BLOB_LIMIT: u32 = 3000;
+const DEFAULT_WINDOW: Duration = Duration::from_secs(300);
+
+/// A token bucket that paces requests to stay under a server's rate limit.
+///
+/// It starts from a configured budget and then follows the `ratelimit-limit`,
+/// `ratelimit-remaining`, `ratelimit-reset` and `ratelimit-policy` headers, so
+/// the budget tracks what the server actually has left, including requests
+/// made by other clients sharing the same quota.
+#[derive(Debug)]
+pub struct RateLimiter {
+    name: String,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    /// Tokens regained per second between window resets
+    refill_rate: f64,
+    window: Duration,
+    last_refill: Instant,
+    /// When the server's current window ends and the full budget is back
+    reset_at: Option<Instant>,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        if self.reset_at.is_some_and(|reset_at| now >= reset_at) {
+            self.tokens = self.capacity;
+            self.reset_at = None;
+        } else {
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        }
+        self.last_refill = now;
+    }
+
+    /// How long until a token is available.
+    fn wait_time(&self, now: Instant) -> Duration {
+        let refill = Duration::from_secs_f64((1.0 - self.tokens) / self.refill_rate);
+        match self.reset_at {
+            Some(reset_at) => refill.min(reset_at.saturating_duration_since(now)),
+            None => refill,
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Allows `limit` requests per `window`, starting with the full budget.
+    pub fn new(name: impl Into<String>, limit: u32, window: Duration) -> Self {
+        let capacity = limit.max(1) as f64;
+        Self {
+            name: name.into(),
+            bucket: Mutex::new(Bucket {
+                capacity,
+                tokens: capacity,
+                refill_rate: capacity / window.as_secs_f64().max(1.0),
+                window,
+                last_refill: Instant::now(),
+                reset_at: None,
+            }),
+        }
+    }
+
+    /// Waits until the budget allows another request, then takes it.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                bucket.refill(now);
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                bucket.wait_time(now)
+            };
+
+            debug!(
+                "{} rate limit budget spent, waiting {:.1}s",
+                self.name,
+                wait.as_secs_f64()
+            );
+            sleep(wait).await;
+        }
+    }
+
+    /// Adjusts the budget to the rate limit headers on a response.
+    pub fn update(&self, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.trim().to_string())
+        };
+        let number = |name: &str| header(name).and_then(|v| v.parse::<f64>().ok());
+
+        let limit = number("ratelimit-limit");
+        let remaining = number("ratelimit-remaining");
+        let reset = number("ratelimit-reset");
+        // e.g. "3000;w=300"
+        let window = header("ratelimit-policy").and_then(|policy| {
+            policy
+                .split(';')
+                .find_map(|part| part.trim().strip_prefix("w="))
+                .and_then(|w| w.parse::<u64>().ok())
+                .map(Duration::from_secs)
+        });
+
+        if limit.is_none() && remaining.is_none() {
+            return;
+        }
+
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        bucket.refill(now);
+
+        if let Some(window) = window {
+            bucket.window = window;
+        }
+        if let Some(limit) = limit.filter(|limit| *limit >= 1.0) {
+            bucket.capacity = limit;
+            bucket.refill_rate = limit / bucket.window.as_secs_f64().max(1.0);
+        }
+        if let Some(remaining) = remaining {
+            // The server's count includes requests we didn't make, so never
+            // assume more is left than it says
+            bucket.tokens = bucket.tokens.min(remaining);
+        }
+        if let Some(reset) = reset {
+            let until = reset - chrono::Utc::now().timestamp() as f64;
+            if until > 0.0 {
+                bucket.reset_at = Some(now + Duration::from_secs_f64(until));
+            }
+        }
+    }
+
+    /// Requests that can be made right now without waiting.
+    pub fn available(&self) -> u32 {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill(Instant::now());
+        bucket.tokens.max(0.0) as u32
+    }
+}
+
+/// Separate budgets for AppView calls and blob downloads, which are limited
+/// independently by the server. Blobs come from each author's own PDS, so
+/// every blob host gets a budget of its own.
+#[derive(Debug)]
+pub struct RateLimits {
+    pub appview: Arc<RateLimiter>,
+    blobs: Mutex<HashMap<String, Arc<RateLimiter>>>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            appview: Arc::new(RateLimiter::new(
+                "AppView",
+                DEFAULT_APPVIEW_LIMIT,
+                DEFAULT_WINDOW,
+            )),
+            blobs: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl RateLimits {
+    /// Picks the budget a request to `url` counts against.
+    ///
+    /// Requests that aren't XRPC calls, such as HLS segments and linked
+    /// media, aren't covered by either budget and return `None`.
+    pub fn for_url(&self, url: &str) -> Option<Arc<RateLimiter>> {
+        let url = Url::parse(url).ok()?;
+        if url.path() == "/xrpc/com.atproto.sync.getBlob" {
+            Some(self.blob_host(&host_key(&url)?))
+        } else if url.path().starts_with("/xrpc/") {
+            Some(self.appview.clone())
+        } else {
+            None
+        }
+    }
+
+    /// The getBlob budget of `host` (e.g. `pds.example.com` or `127.0.0.1:8080`).
+    pub fn blob_host(&self, host: &str) -> Arc<RateLimiter> {
+        self.blobs
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_insert_with(|| {
+                Arc::new(RateLimiter::new(
+                    format!("Blob ({})", host),
+                    DEFAULT_BLOB_LIMIT,
+                    DEFAULT_WINDOW,
+                ))
+            })
+            .clone()
+    }
+}
+
+/// Host of `url`, with the port when it isn't the scheme's default.
+fn host_key(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
@@ -0,0 +1,177 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+use std::future::Future;
+use tokio::time::{sleep, Duration};
+use tracing::warn;
+
+/// Longest wait accepted from a server's Retry-After or ratelimit-reset header.
+const MAX_SERVER_DELAY: Duration = Duration::from_secs(15 * 60);
+
+/// When and how long to wait before retrying a failed request.
+///
+/// Rate-limited (429) and transient server errors (500, 502, 503, 504) are
+/// retried, as are connection failures, timeouts and responses cut off
+/// before the whole body arrived. The wait doubles with each attempt, with
+/// jitter so concurrent requests don't retry in lockstep, unless the server
+/// says when to come back.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 = never retry)
+    pub max_retries: u32,
+    /// Wait before the first retry; doubled for each one after
+    pub base_delay: Duration,
+    /// Upper bound on the backoff when the server gives no hint
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that sends each request exactly once.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::INTERNAL_SERVER_ERROR
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+
+    /// Whether a failed request is worth trying again, as opposed to a bad URL
+    /// or a request that could never succeed.
+    pub fn is_retryable_error(error: &anyhow::Error) -> bool {
+        error
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+            .any(|e| {
+                e.is_connect() || e.is_timeout() || e.is_request() || e.is_body() || e.is_decode()
+            })
+    }
+
+    /// Jittered exponential backoff for the given retry (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let delay = exponential.min(self.max_delay);
+
+        // Wait at least half the delay, and a random amount of the rest
+        let half = delay / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+
+    /// How long to wait before the given retry, preferring the server's hint.
+    ///
+    /// `ratelimit-reset` is only trusted on 429s: it is sent on every response
+    /// and says nothing about when a failing server will recover.
+    pub fn delay(&self, attempt: u32, response: Option<&Response>) -> Duration {
+        response
+            .and_then(|response| {
+                retry_after(response.headers()).or_else(|| {
+                    (response.status() == StatusCode::TOO_MANY_REQUESTS)
+                        .then(|| ratelimit_reset(response.headers()))
+                        .flatten()
+                })
+            })
+            .map(|delay| delay.min(MAX_SERVER_DELAY))
+            .unwrap_or_else(|| self.backoff(attempt))
+    }
+
+    /// Sends a request built by `send`, retrying it under this policy.
+    ///
+    /// `send` is called again for every attempt so credentials and other
+    /// per-request state are fresh. Once retries are exhausted the last
+    /// response is returned as-is for the caller to report.
+    pub async fn send<F, Fut>(&self, description: &str, mut send: F) -> Result<Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let result = send().await;
+            let retries_left = attempt < self.max_retries;
+            attempt += 1;
+
+            let delay = match &result {
+                Ok(response) if retries_left && Self::is_retryable_status(response.status()) => {
+                    let delay = self.delay(attempt, Some(response));
+                    warn!(
+                        "{} returned {}, retrying in {:.1}s ({}/{})",
+                        description,
+                        response.status(),
+                        delay.as_secs_f64(),
+                        attempt,
+                        self.max_retries
+                    );
+                    delay
+                }
+                Err(e) if retries_left && Self::is_retryable_error(e) => {
+                    let delay = self.delay(attempt, None);
+                    warn!(
+                        "{} failed: {}, retrying in {:.1}s ({}/{})",
+                        description,
+                        e,
+                        delay.as_secs_f64(),
+                        attempt,
+                        self.max_retries
+                    );
+                    delay
+                }
+                _ => return result,
+            };
+
+            sleep(delay).await;
+        }
+    }
+}
+
+/// Reads a `Retry-After` header, given either in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| until(date.with_timezone(&Utc)))
+}
+
+/// Reads the `ratelimit-reset` header, a Unix timestamp for when the
+/// current rate limit window ends.
+pub fn ratelimit_reset(headers: &HeaderMap) -> Option<Duration> {
+    let reset = headers
+        .get("ratelimit-reset")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<i64>()
+        .ok()?;
+    DateTime::from_timestamp(reset, 0).map(until)
+}
+
+fn until(at: DateTime<Utc>) -> Duration {
+    (at - Utc::now()).to_std().unwrap_or(Duration::ZERO)
+}
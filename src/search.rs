@@ -0,0 +1,66 @@
+use sha2::{Digest, Sha256};
+
+/// Parameters of an app.bsky.feed.searchPosts query.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// Search text, which may itself contain `#hashtags`, `from:` and other operators
+    pub query: String,
+    /// Only posts by this handle or DID
+    pub author: Option<String>,
+    /// Only posts in this language (e.g. `en`)
+    pub lang: Option<String>,
+    /// Only posts at or after this date or datetime (e.g. `2024-06-01`)
+    pub since: Option<String>,
+    /// Only posts before this date or datetime
+    pub until: Option<String>,
+    /// `top` or `latest`; the server default when unset
+    pub sort: Option<String>,
+    /// Only posts with all of these hashtags (without `#`)
+    pub tags: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Query parameters other than `limit` and `cursor`.
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![("q", self.query.clone())];
+        let optional = [
+            ("author", &self.author),
+            ("lang", &self.lang),
+            ("since", &self.since),
+            ("until", &self.until),
+            ("sort", &self.sort),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                params.push((name, value.clone()));
+            }
+        }
+        for tag in &self.tags {
+            params.push(("tag", tag.trim_start_matches('#').to_string()));
+        }
+        params
+    }
+
+    /// Short name for the query, safe to use in a filename, so each distinct
+    /// search keeps its own resume cursor.
+    pub fn file_key(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (name, value) in self.params() {
+            hasher.update(name);
+            hasher.update([0]);
+            hasher.update(value);
+            hasher.update([0]);
+        }
+        hasher.finalize()[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::oauth::OAuthSession;
+
+/// An authenticated session as returned by createSession/refreshSession.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub did: String,
+    #[serde(rename = "accessJwt")]
+    pub access_jwt: String,
+    #[serde(rename = "refreshJwt")]
+    pub refresh_jwt: String,
+}
+
+/// How requests are authorized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Credentials {
+    /// Bearer tokens from createSession with an app password
+    AppPassword(Session),
+    /// DPoP-bound tokens from the OAuth flow
+    #[serde(rename = "oauth")]
+    OAuth(OAuthSession),
+}
+
+impl Credentials {
+    pub fn did(&self) -> &str {
+        match self {
+            Credentials::AppPassword(session) => &session.did,
+            Credentials::OAuth(session) => &session.did,
+        }
+    }
+
+    pub fn access_token(&self) -> &str {
+        match self {
+            Credentials::AppPassword(session) => &session.access_jwt,
+            Credentials::OAuth(session) => &session.access_token,
+        }
+    }
+}
+
+/// A session saved to disk, along with what it was created for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSession {
+    /// Identifier (handle, DID or email) the session was created with
+    pub identifier: String,
+    /// Service the client was configured to log in to
+    pub service: String,
+    pub credentials: Credentials,
+}
+
+/// Returned when the server refuses a session's refresh token, so only a
+/// fresh login can get a working session again.
+#[derive(Debug)]
+pub struct SessionRejected(pub String);
+
+impl std::fmt::Display for SessionRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SessionRejected {}
+
+/// Persists sessions to a JSON file readable only by the owner.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    path: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn load(&self) -> Result<Option<StoredSession>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&self.path)?;
+        Ok(Some(serde_json::from_str(&contents)?))
+    }
+
+    pub fn save(&self, session: &StoredSession) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so a crash never leaves a truncated session behind
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = open_private(&tmp_path)?;
+        file.write_all(serde_json::to_string_pretty(session)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn open_private(path: &Path) -> Result<fs::File> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // mode() only applies on creation, so tighten a pre-existing file as well
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn open_private(path: &Path) -> Result<fs::File> {
+    Ok(fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?)
+}
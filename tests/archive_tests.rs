@@ -1,28 +1,96 @@
 use bluesky_archiver::archive::Archiver;
-use bluesky_archiver::bluesky::Client;
+use bluesky_archiver::bluesky::{Client, Endpoints, Post};
 use bluesky_archiver::database::Database;
+use std::path::PathBuf;
 use tempfile::tempdir;
 
-async fn setup_test_archiver() -> (
+async fn setup_test_archiver(
+    endpoints: Endpoints,
+) -> (
     Archiver<'static>,
     tempfile::TempDir,
     tempfile::TempDir,
-    Client,
+    PathBuf,
+    &'static Client,
+) {
+    setup_test_archiver_with_client(Client::with_endpoints(endpoints)).await
+}
+
+async fn setup_test_archiver_with_client(
+    client: Client,
+) -> (
+    Archiver<'static>,
+    tempfile::TempDir,
+    tempfile::TempDir,
+    PathBuf,
+    &'static Client,
 ) {
     let output_dir = tempdir().unwrap();
     let db_dir = tempdir().unwrap();
     let db_path = db_dir.path().join("test.db");
     let db = Database::new(&db_path).unwrap();
 
-    let client = Box::leak(Box::new(Client::new()));
+    let client = Box::leak(Box::new(client));
     let archiver = Archiver::new(db, output_dir.path().to_path_buf(), client);
 
-    (archiver, output_dir, db_dir, client.clone())
+    (archiver, output_dir, db_dir, db_path, client)
+}
+
+/// Builds a post by did:plc:author with one image, `bafyblob{n}`.
+fn image_post(n: u32) -> Post {
+    serde_json::from_value(image_post_json(n)).unwrap()
+}
+
+fn image_post_json(n: u32) -> serde_json::Value {
+    serde_json::json!({
+        "uri": format!("at://did:plc:author/app.bsky.feed.post/{}", n),
+        "cid": format!("bafypost{}", n),
+        "author": { "did": "did:plc:author", "handle": "author.test" },
+        "record": {
+            "$type": "app.bsky.feed.post",
+            "createdAt": format!("2024-01-0{}T00:00:00Z", n),
+            "embed": {
+                "$type": "app.bsky.embed.images",
+                "images": [{
+                    "alt": "",
+                    "image": {
+                        "$type": "blob",
+                        "ref": { "$link": format!("bafyblob{}", n) },
+                        "mimeType": "image/png",
+                        "size": 11
+                    }
+                }]
+            }
+        },
+        "indexedAt": "2024-01-01T00:00:00Z"
+    })
+}
+
+/// Serves a DID document for did:plc:author that points back at `server` as its PDS.
+async fn mock_author_did(server: &mut mockito::ServerGuard) {
+    let pds = server.url();
+    server
+        .mock("GET", "/did:plc:author")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "id": "did:plc:author",
+                "service": [{
+                    "id": "#atproto_pds",
+                    "type": "AtprotoPersonalDataServer",
+                    "serviceEndpoint": pds
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
 }
 
 #[tokio::test]
 async fn test_archive_empty_posts() {
-    let (archiver, _output_dir, _db_dir, _client) = setup_test_archiver().await;
+    let (archiver, _output_dir, _db_dir, _db_path, _client) =
+        setup_test_archiver(Endpoints::default()).await;
 
     let stats = archiver.archive_posts(vec![], false).await.unwrap();
     assert_eq!(stats.downloaded, 0);
@@ -32,7 +100,8 @@ async fn test_archive_empty_posts() {
 
 #[tokio::test]
 async fn test_directory_structure_creation() {
-    let (archiver, output_dir, _db_dir, _client) = setup_test_archiver().await;
+    let (archiver, output_dir, _db_dir, _db_path, _client) =
+        setup_test_archiver(Endpoints::default()).await;
 
     let posts = vec![];
     archiver.archive_posts(posts, false).await.unwrap();
@@ -40,3 +109,875 @@ async fn test_directory_structure_creation() {
     let archive_path = output_dir.path();
     assert!(archive_path.exists());
 }
+
+#[tokio::test]
+async fn test_blobs_downloaded_from_author_pds() {
+    let mut pds = mockito::Server::new_async().await;
+    let mut plc = mockito::Server::new_async().await;
+    let mut author_pds = mockito::Server::new_async().await;
+
+    pds.mock("POST", "/xrpc/com.atproto.server.createSession")
+        .with_status(200)
+        .with_body(r#"{"did":"did:plc:me","accessJwt":"access","refreshJwt":"refresh"}"#)
+        .create_async()
+        .await;
+
+    let plc_mock = plc
+        .mock("GET", "/did:plc:author")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "id": "did:plc:author",
+                "service": [{
+                    "id": "#atproto_pds",
+                    "type": "AtprotoPersonalDataServer",
+                    "serviceEndpoint": author_pds.url()
+                }]
+            })
+            .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let blob_mock = author_pds
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("did".into(), "did:plc:author".into()),
+            mockito::Matcher::UrlEncoded("cid".into(), "bafyblob".into()),
+        ]))
+        .match_header("authorization", mockito::Matcher::Missing)
+        .with_status(200)
+        .with_body("image-bytes")
+        .create_async()
+        .await;
+
+    let client = Box::leak(Box::new(Client::with_endpoints(Endpoints {
+        pds: pds.url(),
+        plc_directory: plc.url(),
+        ..Default::default()
+    })));
+    client.login("me.test", "password").await.unwrap();
+
+    let output_dir = tempdir().unwrap();
+    let db_dir = tempdir().unwrap();
+    let db = Database::new(&db_dir.path().join("test.db")).unwrap();
+    let archiver = Archiver::new(db, output_dir.path().to_path_buf(), client);
+
+    let post: Post = serde_json::from_value(serde_json::json!({
+        "uri": "at://did:plc:author/app.bsky.feed.post/1",
+        "cid": "bafypostcid",
+        "author": { "did": "did:plc:author", "handle": "author.test" },
+        "record": {
+            "$type": "app.bsky.feed.post",
+            "createdAt": "2024-01-01T00:00:00Z",
+            "embed": {
+                "$type": "app.bsky.embed.images",
+                "images": [{
+                    "alt": "",
+                    "image": {
+                        "$type": "blob",
+                        "ref": { "$link": "bafyblob" },
+                        "mimeType": "image/png",
+                        "size": 11
+                    }
+                }]
+            }
+        },
+        "indexedAt": "2024-01-01T00:00:00Z"
+    }))
+    .unwrap();
+
+    let stats = archiver.archive_posts(vec![post], false).await.unwrap();
+    assert_eq!(stats.downloaded, 1);
+
+    plc_mock.assert_async().await;
+    blob_mock.assert_async().await;
+
+    let file = output_dir
+        .path()
+        .join("author.test")
+        .join("author.test_2024-01-01T00-00-00Z_bafypost_0.png");
+    assert_eq!(std::fs::read(file).unwrap(), b"image-bytes");
+
+    let db = Database::new(&db_dir.path().join("test.db")).unwrap();
+    let (cached, _) = db.get_pds_endpoint("did:plc:author").unwrap().unwrap();
+    assert_eq!(cached, author_pds.url());
+}
+
+#[tokio::test]
+async fn test_archive_pages_downloads_before_next_page() {
+    use bluesky_archiver::paginator::FeedPage;
+    use futures::stream::{self, StreamExt};
+
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body("image-bytes")
+        .expect(2)
+        .create_async()
+        .await;
+
+    let (archiver, output_dir, _db_dir, _db_path, _client) = setup_test_archiver(Endpoints {
+        plc_directory: server.url(),
+        ..Default::default()
+    })
+    .await;
+
+    let author_dir = output_dir.path().join("author.test");
+    let first_file = author_dir.join("author.test_2024-01-01T00-00-00Z_bafypost_0.png");
+    let first_file_check = first_file.clone();
+
+    let first = FeedPage {
+        items: vec![image_post(1)],
+        cursor: Some("page-2".to_string()),
+    };
+    let second = FeedPage {
+        items: vec![image_post(2)],
+        cursor: None,
+    };
+    let pages = stream::once(async move { Ok(first) }).chain(stream::once(async move {
+        // The first page must be on disk before the second one is pulled
+        assert!(first_file_check.exists());
+        Ok(second)
+    }));
+
+    let stats = archiver.archive_pages(pages, false, None).await.unwrap();
+    assert_eq!(stats.downloaded, 2);
+    assert!(first_file.exists());
+    assert!(author_dir
+        .join("author.test_2024-01-02T00-00-00Z_bafypost_0.png")
+        .exists());
+}
+
+/// Three pages of one image post each, with cursors "page-2" to "page-4".
+fn three_pages(
+) -> impl futures::Stream<Item = anyhow::Result<bluesky_archiver::paginator::FeedPage<Post>>> {
+    use bluesky_archiver::paginator::FeedPage;
+
+    futures::stream::iter((1..=3).map(|n| {
+        Ok(FeedPage {
+            items: vec![image_post(n)],
+            cursor: Some(format!("page-{}", n + 1)),
+        })
+    }))
+}
+
+/// Archives [`three_pages`] with the blob of the second post answered with
+/// `status`, returning the stats, the checkpoints made and the database path.
+async fn archive_with_second_blob_failing(
+    status: usize,
+) -> (
+    bluesky_archiver::archive::ArchiveStats,
+    Vec<String>,
+    PathBuf,
+    tempfile::TempDir,
+) {
+    use std::sync::{Arc, Mutex};
+
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cid".into(),
+            "bafyblob2".into(),
+        ))
+        .with_status(status)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body("image-bytes")
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        plc_directory: server.url(),
+        ..Default::default()
+    });
+    client.set_retry_policy(bluesky_archiver::retry::RetryPolicy::none());
+    let (archiver, _output_dir, db_dir, db_path, _client) =
+        setup_test_archiver_with_client(client).await;
+
+    let checkpoints = Arc::new(Mutex::new(Vec::new()));
+    let saved = checkpoints.clone();
+    let checkpoint_db = db_path.clone();
+    let checkpoint = Box::new(move |cursor: &str| {
+        // Everything before the checkpoint is already in the database
+        let db = Database::new(&checkpoint_db).unwrap();
+        assert!(db.is_image_archived("bafyblob1").unwrap());
+        saved.lock().unwrap().push(cursor.to_string());
+    });
+
+    let stats = archiver
+        .archive_pages(three_pages(), false, Some(checkpoint))
+        .await
+        .unwrap();
+    let checkpoints = checkpoints.lock().unwrap().clone();
+    (stats, checkpoints, db_path, db_dir)
+}
+
+#[tokio::test]
+async fn test_checkpoint_only_after_page_is_archived() {
+    let (stats, checkpoints, db_path, _db_dir) = archive_with_second_blob_failing(503).await;
+    assert_eq!(stats.downloaded, 2);
+    assert_eq!(stats.failed, 1);
+    assert_eq!(stats.retryable, 1);
+
+    // The failed page holds the checkpoint back even though later pages succeeded
+    assert_eq!(checkpoints, vec!["page-2".to_string()]);
+
+    // A later run may succeed, so the failure isn't recorded as permanent
+    let db = Database::new(&db_path).unwrap();
+    let failed = db
+        .get_failed_blobs("at://did:plc:author/app.bsky.feed.post/2")
+        .unwrap();
+    assert!(failed.is_empty());
+}
+
+#[tokio::test]
+async fn test_permanent_failures_are_recorded_and_checkpointed() {
+    let (stats, checkpoints, db_path, _db_dir) = archive_with_second_blob_failing(404).await;
+    assert_eq!(stats.downloaded, 2);
+    assert_eq!(stats.failed, 1);
+    assert_eq!(stats.retryable, 0);
+
+    // A deleted blob won't come back, so resuming moves past it
+    assert_eq!(checkpoints, vec!["page-2", "page-3", "page-4"]);
+
+    let db = Database::new(&db_path).unwrap();
+    let failed = db
+        .get_failed_blobs("at://did:plc:author/app.bsky.feed.post/2")
+        .unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].blob, "bafyblob2");
+    assert_eq!(failed[0].what, "image");
+    assert!(failed[0].error.contains("404"));
+}
+
+#[tokio::test]
+async fn test_archives_record_with_media_images() {
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    let blob_mock = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cid".into(),
+            "bafymedia".into(),
+        ))
+        .with_status(200)
+        .with_body("image-bytes")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (archiver, _output_dir, _db_dir, _db_path, _client) = setup_test_archiver(Endpoints {
+        plc_directory: server.url(),
+        ..Default::default()
+    })
+    .await;
+
+    let post: Post = serde_json::from_value(serde_json::json!({
+        "uri": "at://did:plc:author/app.bsky.feed.post/1",
+        "cid": "bafypostcid",
+        "author": { "did": "did:plc:author", "handle": "author.test" },
+        "record": {
+            "$type": "app.bsky.feed.post",
+            "createdAt": "2024-01-01T00:00:00Z",
+            "embed": {
+                "$type": "app.bsky.embed.recordWithMedia",
+                "record": {
+                    "$type": "app.bsky.embed.record",
+                    "record": { "uri": "at://did:plc:other/app.bsky.feed.post/1", "cid": "bafyquoted" }
+                },
+                "media": {
+                    "$type": "app.bsky.embed.images",
+                    "images": [{
+                        "alt": "",
+                        "image": {
+                            "$type": "blob",
+                            "ref": { "$link": "bafymedia" },
+                            "mimeType": "image/jpeg",
+                            "size": 11
+                        }
+                    }]
+                }
+            }
+        },
+        "indexedAt": "2024-01-01T00:00:00Z"
+    }))
+    .unwrap();
+
+    let stats = archiver.archive_posts(vec![post], false).await.unwrap();
+    assert_eq!(stats.downloaded, 1);
+    blob_mock.assert_async().await;
+}
+
+fn video_post() -> Post {
+    serde_json::from_value(serde_json::json!({
+        "uri": "at://did:plc:author/app.bsky.feed.post/video",
+        "cid": "bafyvideopost",
+        "author": { "did": "did:plc:author", "handle": "author.test" },
+        "record": {
+            "$type": "app.bsky.feed.post",
+            "createdAt": "2024-01-01T00:00:00Z",
+            "embed": {
+                "$type": "app.bsky.embed.video",
+                "video": {
+                    "$type": "blob",
+                    "ref": { "$link": "bafyvideo" },
+                    "mimeType": "video/mp4",
+                    "size": 10
+                },
+                "captions": [{
+                    "lang": "en",
+                    "file": {
+                        "$type": "blob",
+                        "ref": { "$link": "bafycaptions" },
+                        "mimeType": "text/vtt",
+                        "size": 6
+                    }
+                }],
+                "alt": "A short clip",
+                "aspectRatio": { "width": 16, "height": 9 }
+            }
+        },
+        "indexedAt": "2024-01-01T00:00:00Z"
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_archives_video_blob_with_captions() {
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cid".into(),
+            "bafyvideo".into(),
+        ))
+        .with_status(200)
+        .with_body("video-data")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cid".into(),
+            "bafycaptions".into(),
+        ))
+        .with_status(200)
+        .with_body("WEBVTT")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/watch/did%3Aplc%3Aauthor/bafyvideo/playlist.m3u8")
+        .with_status(200)
+        .with_body("#EXTM3U\n#EXTINF:3.0,\nseg0.ts\n#EXTINF:1.5,\nseg1.ts\n")
+        .create_async()
+        .await;
+
+    let (archiver, output_dir, _db_dir, db_path, client) = setup_test_archiver(Endpoints {
+        plc_directory: server.url(),
+        video: server.url(),
+        ..Default::default()
+    })
+    .await;
+
+    let stats = archiver
+        .archive_posts(vec![video_post()], false)
+        .await
+        .unwrap();
+    assert_eq!(stats.downloaded, 1);
+    assert_eq!(stats.failed, 0);
+
+    let author_dir = output_dir.path().join("author.test");
+    let prefix = "author.test_2024-01-01T00-00-00Z_bafyvide_video";
+    assert_eq!(
+        std::fs::read(author_dir.join(format!("{}.mp4", prefix))).unwrap(),
+        b"video-data"
+    );
+    assert_eq!(
+        std::fs::read(author_dir.join(format!("{}.en.vtt", prefix))).unwrap(),
+        b"WEBVTT"
+    );
+
+    let db = Database::new(&db_path).unwrap();
+    let video = db.get_video("bafyvideo").unwrap().unwrap();
+    assert_eq!(video.source, "blob");
+    assert_eq!(video.duration, Some(4.5));
+    assert_eq!(
+        (video.aspect_width, video.aspect_height),
+        (Some(16), Some(9))
+    );
+    assert_eq!(video.alt_text.as_deref(), Some("A short clip"));
+
+    // Already archived videos are skipped
+    let archiver = Archiver::new(db, output_dir.path().to_path_buf(), client);
+    let stats = archiver
+        .archive_posts(vec![video_post()], false)
+        .await
+        .unwrap();
+    assert_eq!(stats.skipped, 1);
+}
+
+#[tokio::test]
+async fn test_video_falls_back_to_hls() {
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let watch = "/watch/did%3Aplc%3Aauthor/bafyvideo";
+    server
+        .mock("GET", format!("{}/playlist.m3u8", watch).as_str())
+        .with_status(200)
+        .with_body(
+            "#EXTM3U\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=500000\nlow/video.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=2000000\nhigh/video.m3u8\n",
+        )
+        .create_async()
+        .await;
+    server
+        .mock("GET", format!("{}/high/video.m3u8", watch).as_str())
+        .with_status(200)
+        .with_body("#EXTM3U\n#EXTINF:2.0,\nseg0.ts\n#EXTINF:2.0,\nseg1.ts\n#EXT-X-ENDLIST\n")
+        .create_async()
+        .await;
+    server
+        .mock("GET", format!("{}/high/seg0.ts", watch).as_str())
+        .with_status(200)
+        .with_body("first-")
+        .create_async()
+        .await;
+    server
+        .mock("GET", format!("{}/high/seg1.ts", watch).as_str())
+        .with_status(200)
+        .with_body("second")
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        plc_directory: server.url(),
+        video: server.url(),
+        ..Default::default()
+    });
+    client.set_retry_policy(bluesky_archiver::retry::RetryPolicy::none());
+    let (archiver, output_dir, _db_dir, db_path, _client) =
+        setup_test_archiver_with_client(client).await;
+
+    let stats = archiver
+        .archive_posts(vec![video_post()], false)
+        .await
+        .unwrap();
+    assert_eq!(stats.downloaded, 1);
+
+    let file = output_dir
+        .path()
+        .join("author.test")
+        .join("author.test_2024-01-01T00-00-00Z_bafyvide_video.ts");
+    assert_eq!(std::fs::read(file).unwrap(), b"first-second");
+
+    let db = Database::new(&db_path).unwrap();
+    let video = db.get_video("bafyvideo").unwrap().unwrap();
+    assert_eq!(video.source, "hls");
+    assert_eq!(video.mime_type, "video/mp2t");
+    assert_eq!(video.duration, Some(4.0));
+}
+
+#[tokio::test]
+async fn test_archives_images_in_quoted_posts() {
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    let blob_mock = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cid".into(),
+            "bafyblob1".into(),
+        ))
+        .with_status(200)
+        .with_body("image-bytes")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (archiver, output_dir, _db_dir, db_path, _client) = setup_test_archiver(Endpoints {
+        plc_directory: server.url(),
+        ..Default::default()
+    })
+    .await;
+
+    // The quoted post's record, as hydrated in the quoting post's embed view
+    let quoted = image_post_json(1);
+    let quote: Post = serde_json::from_value(serde_json::json!({
+        "uri": "at://did:plc:quoter/app.bsky.feed.post/q",
+        "cid": "bafyquotepost",
+        "author": { "did": "did:plc:quoter", "handle": "quoter.test" },
+        "record": {
+            "$type": "app.bsky.feed.post",
+            "text": "look at this",
+            "createdAt": "2024-02-01T00:00:00Z",
+            "embed": {
+                "$type": "app.bsky.embed.record",
+                "record": { "uri": quoted["uri"], "cid": quoted["cid"] }
+            }
+        },
+        "embed": {
+            "$type": "app.bsky.embed.record#view",
+            "record": {
+                "$type": "app.bsky.embed.record#viewRecord",
+                "uri": quoted["uri"],
+                "cid": quoted["cid"],
+                "author": { "did": "did:plc:author", "handle": "author.test" },
+                "value": quoted["record"],
+                "indexedAt": "2024-01-01T00:00:00Z"
+            }
+        },
+        "indexedAt": "2024-02-01T00:00:00Z"
+    }))
+    .unwrap();
+
+    let stats = archiver.archive_posts(vec![quote], false).await.unwrap();
+    assert_eq!(stats.downloaded, 1);
+    blob_mock.assert_async().await;
+
+    assert!(output_dir
+        .path()
+        .join("author.test")
+        .join("author.test_2024-01-01T00-00-00Z_bafypost_0.png")
+        .exists());
+
+    let db = Database::new(&db_path).unwrap();
+    assert!(db
+        .is_post_archived("at://did:plc:quoter/app.bsky.feed.post/q")
+        .unwrap());
+    assert_eq!(
+        db.get_quoted_posts("at://did:plc:quoter/app.bsky.feed.post/q")
+            .unwrap(),
+        vec!["at://did:plc:author/app.bsky.feed.post/1".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_archives_external_thumbnails_and_gifs() {
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    let thumb_mock = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cid".into(),
+            "bafythumb".into(),
+        ))
+        .with_status(200)
+        .with_body("thumb-bytes")
+        .expect(1)
+        .create_async()
+        .await;
+    let gif_mock = server
+        .mock("GET", "/m/AAAA/dancing.gif")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_header("content-type", "image/gif")
+        .with_body("gif-bytes")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (mut archiver, output_dir, _db_dir, db_path, _client) = setup_test_archiver(Endpoints {
+        plc_directory: server.url(),
+        ..Default::default()
+    })
+    .await;
+
+    let gif_url = format!("{}/m/AAAA/dancing.gif?hh=280&ww=498", server.url());
+    let gif_post = || -> Post {
+        serde_json::from_value(serde_json::json!({
+            "uri": "at://did:plc:author/app.bsky.feed.post/gif",
+            "cid": "bafygifpost",
+            "author": { "did": "did:plc:author", "handle": "author.test" },
+            "record": {
+                "$type": "app.bsky.feed.post",
+                "createdAt": "2024-01-01T00:00:00Z",
+                "embed": {
+                    "$type": "app.bsky.embed.external",
+                    "external": {
+                        "uri": gif_url,
+                        "title": "Dancing",
+                        "description": "Alt: a dancing cat",
+                        "thumb": {
+                            "$type": "blob",
+                            "ref": { "$link": "bafythumb" },
+                            "mimeType": "image/jpeg",
+                            "size": 11
+                        }
+                    }
+                }
+            },
+            "indexedAt": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap()
+    };
+
+    // Link cards are ignored unless enabled
+    let stats = archiver
+        .archive_posts(vec![gif_post()], false)
+        .await
+        .unwrap();
+    assert_eq!(stats.downloaded, 0);
+
+    archiver.set_archive_externals(true);
+    let stats = archiver
+        .archive_posts(vec![gif_post()], false)
+        .await
+        .unwrap();
+    assert_eq!(stats.downloaded, 2);
+    thumb_mock.assert_async().await;
+    gif_mock.assert_async().await;
+
+    let author_dir = output_dir.path().join("author.test");
+    let prefix = "author.test_2024-01-01T00-00-00Z_bafygifp";
+    assert_eq!(
+        std::fs::read(author_dir.join(format!("{}_thumb.jpg", prefix))).unwrap(),
+        b"thumb-bytes"
+    );
+    assert_eq!(
+        std::fs::read(author_dir.join(format!("{}_link.gif", prefix))).unwrap(),
+        b"gif-bytes"
+    );
+
+    let db = Database::new(&db_path).unwrap();
+    let external = db
+        .get_external("at://did:plc:author/app.bsky.feed.post/gif")
+        .unwrap()
+        .unwrap();
+    assert_eq!(external.uri, gif_url);
+    assert_eq!(external.title, "Dancing");
+    assert_eq!(external.description, "Alt: a dancing cat");
+    assert_eq!(external.thumb_cid.as_deref(), Some("bafythumb"));
+}
+
+#[tokio::test]
+async fn test_records_post_source() {
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    let blob_mock = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body("image-bytes")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (mut archiver, _output_dir, _db_dir, db_path, _client) = setup_test_archiver(Endpoints {
+        plc_directory: server.url(),
+        ..Default::default()
+    })
+    .await;
+    archiver.set_source("timeline");
+
+    let text_only: Post = serde_json::from_value(serde_json::json!({
+        "uri": "at://did:plc:author/app.bsky.feed.post/text",
+        "cid": "bafytextonly",
+        "author": { "did": "did:plc:author", "handle": "author.test" },
+        "record": { "$type": "app.bsky.feed.post", "createdAt": "2024-01-01T00:00:00Z" },
+        "indexedAt": "2024-01-01T00:00:00Z"
+    }))
+    .unwrap();
+
+    // The second time the post is seen, its image is deduplicated but the
+    // post is still recorded as coming from the timeline
+    let stats = archiver
+        .archive_posts(vec![image_post(1), text_only, image_post(1)], false)
+        .await
+        .unwrap();
+    assert_eq!(stats.downloaded, 1);
+    assert_eq!(stats.skipped, 1);
+    blob_mock.assert_async().await;
+
+    let db = Database::new(&db_path).unwrap();
+    assert_eq!(
+        db.get_post_sources("at://did:plc:author/app.bsky.feed.post/1")
+            .unwrap(),
+        vec!["timeline".to_string()]
+    );
+    assert!(db
+        .get_post_sources("at://did:plc:author/app.bsky.feed.post/text")
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_records_reposts() {
+    use bluesky_archiver::bluesky::{Author, Repost};
+    use bluesky_archiver::database::ArchivedRepost;
+    use bluesky_archiver::paginator::FeedPage;
+    use futures::stream;
+
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body("image-bytes")
+        .create_async()
+        .await;
+
+    let (archiver, _output_dir, _db_dir, db_path, _client) = setup_test_archiver(Endpoints {
+        plc_directory: server.url(),
+        ..Default::default()
+    })
+    .await;
+
+    let curator = Author {
+        did: "did:plc:curator".to_string(),
+        handle: "curator.test".to_string(),
+        display_name: None,
+    };
+    let repost = |post: Post, reposted_at: &str| Repost {
+        post,
+        by: curator.clone(),
+        reposted_at: reposted_at.to_string(),
+    };
+    let mut text_only = image_post_json(2);
+    text_only["record"].as_object_mut().unwrap().remove("embed");
+
+    let pages = stream::iter(vec![Ok(FeedPage {
+        items: vec![
+            repost(image_post(1), "2024-02-01T00:00:00Z"),
+            repost(
+                serde_json::from_value(text_only).unwrap(),
+                "2024-02-02T00:00:00Z",
+            ),
+        ],
+        cursor: None,
+    })]);
+    let stats = archiver.archive_pages(pages, false, None).await.unwrap();
+    assert_eq!(stats.downloaded, 1);
+
+    let db = Database::new(&db_path).unwrap();
+    assert_eq!(
+        db.get_reposts("at://did:plc:author/app.bsky.feed.post/1")
+            .unwrap(),
+        vec![ArchivedRepost {
+            post_uri: "at://did:plc:author/app.bsky.feed.post/1".to_string(),
+            reposted_by_did: "did:plc:curator".to_string(),
+            reposted_by_handle: "curator.test".to_string(),
+            reposted_at: "2024-02-01T00:00:00Z".to_string(),
+        }]
+    );
+    // Text-only posts aren't archived, so their reposts aren't either
+    assert!(db
+        .get_reposts("at://did:plc:author/app.bsky.feed.post/2")
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_archives_thread_with_relationships() {
+    use bluesky_archiver::aturi::AtUri;
+    use bluesky_archiver::database::ThreadLink;
+
+    let mut server = mockito::Server::new_async().await;
+    mock_author_did(&mut server).await;
+    let blob_mock = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body("image-bytes")
+        .expect(2)
+        .create_async()
+        .await;
+
+    let uri = |n: u32| format!("at://did:plc:author/app.bsky.feed.post/{}", n);
+    let strong_ref = |n: u32| serde_json::json!({ "uri": uri(n), "cid": format!("bafypost{}", n) });
+    let reply = |n: u32, parent: u32| {
+        let mut post = image_post_json(n);
+        post["record"]["reply"] =
+            serde_json::json!({ "root": strong_ref(1), "parent": strong_ref(parent) });
+        post
+    };
+    let mut text_reply = reply(3, 2);
+    text_reply["record"]
+        .as_object_mut()
+        .unwrap()
+        .remove("embed");
+
+    // Post 2 is the requested post: its parent is the root, and it has a
+    // text-only reply that has a reply with an image of its own
+    let thread = serde_json::json!({
+        "thread": {
+            "$type": "app.bsky.feed.defs#threadViewPost",
+            "post": reply(2, 1),
+            "parent": {
+                "$type": "app.bsky.feed.defs#threadViewPost",
+                "post": image_post_json(1)
+            },
+            "replies": [
+                {
+                    "$type": "app.bsky.feed.defs#threadViewPost",
+                    "post": text_reply,
+                    "replies": [{
+                        "$type": "app.bsky.feed.defs#notFoundPost",
+                        "uri": uri(9),
+                        "notFound": true
+                    }]
+                },
+                {
+                    "$type": "app.bsky.feed.defs#blockedPost",
+                    "uri": uri(8),
+                    "blocked": true
+                }
+            ]
+        }
+    });
+    let thread_mock = server
+        .mock("GET", "/xrpc/app.bsky.feed.getPostThread")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("uri".into(), uri(2)),
+            mockito::Matcher::UrlEncoded("depth".into(), "10".into()),
+            mockito::Matcher::UrlEncoded("parentHeight".into(), "5".into()),
+        ]))
+        .with_status(200)
+        .with_body(thread.to_string())
+        .create_async()
+        .await;
+
+    let (archiver, _output_dir, _db_dir, db_path, client) = setup_test_archiver(Endpoints {
+        appview: server.url(),
+        plc_directory: server.url(),
+        ..Default::default()
+    })
+    .await;
+
+    let post = AtUri::parse(&uri(2)).unwrap();
+    let thread = client.get_post_thread(&post, 10, 5).await.unwrap();
+    let stats = archiver.archive_thread(thread, false).await.unwrap();
+    assert_eq!(stats.downloaded, 2);
+    thread_mock.assert_async().await;
+    blob_mock.assert_async().await;
+
+    let db = Database::new(&db_path).unwrap();
+    let link = |n: u32, parent: Option<u32>| ThreadLink {
+        post_uri: uri(n),
+        parent_uri: parent.map(uri),
+        root_uri: uri(1),
+    };
+    assert_eq!(
+        db.get_thread(&uri(1)).unwrap(),
+        vec![link(1, None), link(2, Some(1)), link(3, Some(2))]
+    );
+}
@@ -0,0 +1,48 @@
+use bluesky_archiver::aturi::{self, AtUri};
+
+#[test]
+fn test_parse_at_uri() {
+    let uri = AtUri::parse("at://did:plc:curator/app.bsky.feed.generator/art").unwrap();
+    assert_eq!(uri.authority, "did:plc:curator");
+    assert_eq!(uri.collection, aturi::FEED_GENERATOR);
+    assert_eq!(uri.rkey, "art");
+    assert!(uri.has_did());
+    assert_eq!(
+        uri.to_string(),
+        "at://did:plc:curator/app.bsky.feed.generator/art"
+    );
+
+    assert!(AtUri::parse("at://did:plc:curator").is_err());
+    assert!(AtUri::parse("at://did:plc:curator/app.bsky.feed.generator/art/extra").is_err());
+    assert!(AtUri::parse("curator.test/art").is_err());
+}
+
+#[test]
+fn test_parse_bsky_app_urls() {
+    let feed = AtUri::parse("https://bsky.app/profile/curator.test/feed/art").unwrap();
+    assert_eq!(feed.authority, "curator.test");
+    assert_eq!(feed.collection, aturi::FEED_GENERATOR);
+    assert_eq!(feed.rkey, "art");
+    assert!(!feed.has_did());
+
+    let list = AtUri::parse("https://bsky.app/profile/did:plc:curator/lists/3kabc/").unwrap();
+    assert_eq!(list.collection, aturi::LIST);
+    assert_eq!(list.rkey, "3kabc");
+
+    let post = AtUri::parse("https://bsky.app/profile/artist.test/post/3kxyz").unwrap();
+    assert_eq!(post.collection, aturi::POST);
+
+    assert!(AtUri::parse("https://bsky.app/profile/artist.test").is_err());
+}
+
+#[test]
+fn test_expect_collection_and_file_key() {
+    let feed = AtUri::parse("at://did:plc:curator/app.bsky.feed.generator/art").unwrap();
+    assert_eq!(feed.file_key(), "did_plc_curator_art");
+    assert!(feed.clone().expect(aturi::FEED_GENERATOR).is_ok());
+
+    let err = feed.expect(aturi::LIST).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Expected a app.bsky.graph.list URI"));
+}
@@ -1,4 +1,4 @@
-use bluesky_archiver::bluesky::{Client, Embed, Post};
+use bluesky_archiver::bluesky::{AuthFactorTokenRequired, Client, Embed, Endpoints, Post};
 use serde_json::json;
 
 #[tokio::test]
@@ -145,3 +145,1261 @@ async fn test_blob_ref_parsing() {
     assert_eq!(view.mime_type, "image/jpeg");
     assert_eq!(view.size, 123456);
 }
+
+#[tokio::test]
+async fn test_blob_downloads_are_anonymous() {
+    let mut pds = mockito::Server::new_async().await;
+    pds.mock("POST", "/xrpc/com.atproto.server.createSession")
+        .with_status(200)
+        .with_body(
+            json!({ "did": "did:plc:tester", "accessJwt": "access-token", "refreshJwt": "refresh-token" })
+                .to_string(),
+        )
+        .create_async()
+        .await;
+    // Even the blob host we log in to gets no token
+    let blob_mock = pds
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .match_header("authorization", mockito::Matcher::Missing)
+        .with_status(200)
+        .with_body("bytes")
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        pds: pds.url(),
+        blob_host: pds.url(),
+        ..Default::default()
+    });
+    client.login("tester.test", "password").await.unwrap();
+
+    let bytes = client
+        .download_image(&client.get_image_url("did:plc:tester", "bafyblob"))
+        .await
+        .unwrap();
+    assert_eq!(bytes, b"bytes");
+    blob_mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_linked_media_must_be_small_media() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/dancing.gif")
+        .with_status(200)
+        .with_header("content-type", "image/gif")
+        .with_body("gif-bytes")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/page.gif")
+        .with_status(200)
+        .with_header("content-type", "text/html")
+        .with_body("<html></html>")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/large.gif")
+        .with_status(200)
+        .with_header("content-type", "image/gif")
+        .with_body(vec![0; 64])
+        .create_async()
+        .await;
+    // No Content-Length, so the size is only known while streaming
+    server
+        .mock("GET", "/streamed.mp4")
+        .with_status(200)
+        .with_header("content-type", "video/mp4")
+        .with_chunked_body(|w| {
+            for _ in 0..8 {
+                w.write_all(&[0; 16])?;
+            }
+            Ok(())
+        })
+        .create_async()
+        .await;
+
+    let client = Client::new();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("media");
+
+    let size = client
+        .download_linked_media(&format!("{}/dancing.gif", server.url()), &path, 32)
+        .await
+        .unwrap();
+    assert_eq!(size, 9);
+    assert_eq!(std::fs::read(&path).unwrap(), b"gif-bytes");
+
+    for name in ["page.gif", "large.gif", "streamed.mp4"] {
+        let url = format!("{}/{}", server.url(), name);
+        let path = dir.path().join(name);
+        let result = client.download_linked_media(&url, &path, 32).await;
+        assert!(result.is_err(), "{} should be refused", name);
+        assert!(!path.exists(), "{} should leave no file behind", name);
+    }
+}
+
+#[tokio::test]
+async fn test_custom_endpoints() {
+    let mut pds = mockito::Server::new_async().await;
+    let mut appview = mockito::Server::new_async().await;
+
+    let login_mock = pds
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            json!({
+                "did": "did:plc:tester",
+                "handle": "tester.test",
+                "accessJwt": "access-token",
+                "refreshJwt": "refresh-token"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let likes_mock = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getActorLikes")
+        .match_query(mockito::Matcher::Any)
+        .match_header("authorization", "Bearer access-token")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "feed": [] }).to_string())
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        pds: pds.url(),
+        blob_host: "https://blobs.example".to_string(),
+        ..Default::default()
+    });
+
+    client.login("tester.test", "password").await.unwrap();
+    let posts = client
+        .get_likes_with_options("tester.test", 10, 0, None, None)
+        .await
+        .unwrap();
+    assert!(posts.is_empty());
+
+    login_mock.assert_async().await;
+    likes_mock.assert_async().await;
+
+    assert_eq!(
+        client.get_image_url("did:plc:tester", "bafyblob"),
+        "https://blobs.example/xrpc/com.atproto.sync.getBlob?did=did:plc:tester&cid=bafyblob"
+    );
+}
+
+#[tokio::test]
+async fn test_expired_token_refreshes_and_keeps_cursor() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .with_status(200)
+        .with_body(
+            json!({ "did": "did:plc:tester", "accessJwt": "old-access", "refreshJwt": "refresh-1" })
+                .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let refresh_mock = server
+        .mock("POST", "/xrpc/com.atproto.server.refreshSession")
+        .match_header("authorization", "Bearer refresh-1")
+        .with_status(200)
+        .with_body(
+            json!({ "did": "did:plc:tester", "accessJwt": "new-access", "refreshJwt": "refresh-2" })
+                .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let post = |n: u32| {
+        json!({
+            "post": {
+                "uri": format!("at://did:plc:author/app.bsky.feed.post/{}", n),
+                "cid": format!("cid{}", n),
+                "author": { "did": "did:plc:author", "handle": "author.test" },
+                "record": { "$type": "app.bsky.feed.post", "createdAt": "2024-01-01T00:00:00Z" },
+                "indexedAt": "2024-01-01T00:00:00Z"
+            }
+        })
+    };
+
+    let first_page = server
+        .mock("GET", "/xrpc/app.bsky.feed.getActorLikes")
+        .match_query(mockito::Matcher::Regex("^actor=tester&limit=100$".into()))
+        .match_header("authorization", "Bearer old-access")
+        .with_status(200)
+        .with_body(json!({ "feed": [post(1)], "cursor": "page-2" }).to_string())
+        .create_async()
+        .await;
+
+    let expired = server
+        .mock("GET", "/xrpc/app.bsky.feed.getActorLikes")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cursor".into(),
+            "page-2".into(),
+        ))
+        .match_header("authorization", "Bearer old-access")
+        .with_status(400)
+        .with_body(json!({ "error": "ExpiredToken", "message": "Token has expired" }).to_string())
+        .create_async()
+        .await;
+
+    let second_page = server
+        .mock("GET", "/xrpc/app.bsky.feed.getActorLikes")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cursor".into(),
+            "page-2".into(),
+        ))
+        .match_header("authorization", "Bearer new-access")
+        .with_status(200)
+        .with_body(json!({ "feed": [post(2)] }).to_string())
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        appview: server.url(),
+        pds: server.url(),
+        ..Default::default()
+    });
+    client.login("tester", "password").await.unwrap();
+
+    let posts = client
+        .get_likes_with_options("tester", 0, 0, None, None)
+        .await
+        .unwrap();
+    let uris: Vec<_> = posts.iter().map(|p| p.uri.as_str()).collect();
+    assert_eq!(
+        uris,
+        vec![
+            "at://did:plc:author/app.bsky.feed.post/1",
+            "at://did:plc:author/app.bsky.feed.post/2"
+        ]
+    );
+
+    first_page.assert_async().await;
+    expired.assert_async().await;
+    refresh_mock.assert_async().await;
+    second_page.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_non_expired_errors_are_passed_through() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .with_status(200)
+        .with_body(
+            json!({ "did": "did:plc:tester", "accessJwt": "a", "refreshJwt": "r" }).to_string(),
+        )
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/xrpc/app.bsky.feed.getActorLikes")
+        .match_query(mockito::Matcher::Any)
+        .with_status(400)
+        .with_body(json!({ "error": "InvalidRequest", "message": "Profile not found" }).to_string())
+        .create_async()
+        .await;
+    let refresh_mock = server
+        .mock("POST", "/xrpc/com.atproto.server.refreshSession")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        appview: server.url(),
+        pds: server.url(),
+        ..Default::default()
+    });
+    client.login("tester", "password").await.unwrap();
+
+    let err = client
+        .get_likes_with_options("missing", 10, 0, None, None)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("400"), "{}", err);
+    assert!(err.contains("Profile not found"), "{}", err);
+    refresh_mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_login_with_auth_factor_token() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .match_body(mockito::Matcher::Json(
+            json!({ "identifier": "tester", "password": "password" }),
+        ))
+        .with_status(401)
+        .with_body(
+            json!({
+                "error": "AuthFactorTokenRequired",
+                "message": "A sign in code has been sent to your email address"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    let with_token = server
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .match_body(mockito::Matcher::Json(json!({
+            "identifier": "tester",
+            "password": "password",
+            "authFactorToken": "ABCDE-12345"
+        })))
+        .with_status(200)
+        .with_body(
+            json!({ "did": "did:plc:tester", "accessJwt": "a", "refreshJwt": "r" }).to_string(),
+        )
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        pds: server.url(),
+        ..Default::default()
+    });
+
+    let err = client.login("tester", "password").await.unwrap_err();
+    assert!(err.is::<AuthFactorTokenRequired>());
+    assert!(err.to_string().contains("Login failed"));
+
+    client
+        .login_with_auth_factor("tester", "password", Some(" ABCDE-12345\n"))
+        .await
+        .unwrap();
+    with_token.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_public_reads_without_login() {
+    let mut appview = mockito::Server::new_async().await;
+
+    let feed_mock = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "actor".into(),
+            "artist.test".into(),
+        ))
+        .match_header("authorization", mockito::Matcher::Missing)
+        .with_status(200)
+        .with_body(
+            json!({
+                "feed": [{
+                    "post": {
+                        "uri": "at://did:plc:artist/app.bsky.feed.post/1",
+                        "cid": "bafypost",
+                        "author": { "did": "did:plc:artist", "handle": "artist.test" },
+                        "record": {
+                            "$type": "app.bsky.feed.post",
+                            "createdAt": "2024-01-01T00:00:00Z",
+                            "embed": {
+                                "$type": "app.bsky.embed.images",
+                                "images": [{
+                                    "alt": "",
+                                    "image": {
+                                        "$type": "blob",
+                                        "ref": { "$link": "bafyblob" },
+                                        "mimeType": "image/jpeg",
+                                        "size": 5
+                                    }
+                                }]
+                            }
+                        },
+                        "indexedAt": "2024-01-01T00:00:00Z"
+                    }
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    let blob_mock = appview
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .match_header("authorization", mockito::Matcher::Missing)
+        .with_status(200)
+        .with_body("bytes")
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        blob_host: appview.url(),
+        ..Default::default()
+    });
+
+    let posts = client
+        .get_user_posts_with_options("artist.test", 10, 0, None, None)
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+
+    let bytes = client
+        .download_image(&client.get_image_url("did:plc:artist", "bafyblob"))
+        .await
+        .unwrap();
+    assert_eq!(bytes, b"bytes");
+
+    feed_mock.assert_async().await;
+    blob_mock.assert_async().await;
+
+    // Likes are private to the account, so they still need a session
+    let likes = Client::public()
+        .get_likes_with_options("artist.test", 10, 0, None, None)
+        .await;
+    assert!(likes.unwrap_err().to_string().contains("Not authenticated"));
+}
+
+#[tokio::test]
+async fn test_paginator_streams_pages_lazily() {
+    use bluesky_archiver::paginator::PaginateOptions;
+    use futures::StreamExt;
+    use std::sync::{Arc, Mutex};
+
+    let mut appview = mockito::Server::new_async().await;
+
+    let item = |n: u32, repost: bool| {
+        let mut item = json!({
+            "post": {
+                "uri": format!("at://did:plc:artist/app.bsky.feed.post/{}", n),
+                "cid": format!("cid{}", n),
+                "author": { "did": "did:plc:artist", "handle": "artist.test" },
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "createdAt": "2024-01-01T00:00:00Z",
+                    "embed": {
+                        "$type": "app.bsky.embed.images",
+                        "images": [{
+                            "alt": "",
+                            "image": {
+                                "$type": "blob",
+                                "ref": { "$link": format!("blob{}", n) },
+                                "mimeType": "image/jpeg",
+                                "size": 5
+                            }
+                        }]
+                    }
+                },
+                "indexedAt": "2024-01-01T00:00:00Z"
+            }
+        });
+        if repost {
+            item["reason"] = json!({
+                "$type": "app.bsky.feed.defs#reasonRepost",
+                "by": { "did": "did:plc:artist", "handle": "artist.test" },
+                "indexedAt": "2024-01-02T00:00:00Z"
+            });
+        }
+        item
+    };
+
+    let first_page = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Regex(
+            "^actor=artist.test&filter=posts_with_media&limit=3$".into(),
+        ))
+        .with_status(200)
+        .with_body(
+            json!({ "feed": [item(1, false), item(2, true)], "cursor": "page-2" }).to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+    let second_page = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cursor".into(),
+            "page-2".into(),
+        ))
+        .with_status(200)
+        .with_body(
+            json!({ "feed": [item(3, false), item(4, false), item(5, false)], "cursor": "page-3" })
+                .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+    let third_page = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "cursor".into(),
+            "page-3".into(),
+        ))
+        .with_status(200)
+        .with_body(json!({ "feed": [] }).to_string())
+        .expect(0)
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+
+    let saved = Arc::new(Mutex::new(Vec::new()));
+    let saved_clone = saved.clone();
+    let options = PaginateOptions {
+        limit: 3,
+        cursor_callback: Some(Box::new(move |cursor: &str| {
+            saved_clone.lock().unwrap().push(cursor.to_string());
+        })),
+        progress: false,
+        ..Default::default()
+    };
+
+    let mut pages = Box::pin(client.user_posts_pages("artist.test", options));
+
+    // The repost is filtered out, and the next page is not requested yet
+    let page = pages.next().await.unwrap().unwrap();
+    let uris: Vec<_> = page.items.iter().map(|p| p.uri.as_str()).collect();
+    assert_eq!(uris, vec!["at://did:plc:artist/app.bsky.feed.post/1"]);
+    assert_eq!(page.cursor.as_deref(), Some("page-2"));
+    first_page.assert_async().await;
+    assert!(!second_page.matched_async().await);
+
+    // The limit cuts the second page short and ends the stream
+    let page = pages.next().await.unwrap().unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.cursor, None);
+    assert!(pages.next().await.is_none());
+
+    second_page.assert_async().await;
+    third_page.assert_async().await;
+    assert_eq!(*saved.lock().unwrap(), vec!["page-2".to_string()]);
+}
+
+#[tokio::test]
+async fn test_record_with_media_images() {
+    let image = json!({
+        "alt": "attached",
+        "image": {
+            "$type": "blob",
+            "ref": { "$link": "bafymedia" },
+            "mimeType": "image/jpeg",
+            "size": 5
+        }
+    });
+    let quote = json!({
+        "$type": "app.bsky.embed.record",
+        "record": { "uri": "at://did:plc:other/app.bsky.feed.post/1", "cid": "bafyquoted" }
+    });
+    let with_media = json!({
+        "$type": "app.bsky.embed.recordWithMedia",
+        "record": quote.clone(),
+        "media": { "$type": "app.bsky.embed.images", "images": [image] }
+    });
+
+    let embed: Embed = serde_json::from_value(with_media.clone()).unwrap();
+    assert!(matches!(embed, Embed::RecordWithMedia { .. }));
+    let images = embed.images();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].image.ref_.link, "bafymedia");
+
+    let plain_quote: Embed = serde_json::from_value(quote.clone()).unwrap();
+    assert!(plain_quote.images().is_empty());
+
+    // Quote posts with media are kept in a user's feed; plain quotes are still skipped
+    let mut appview = mockito::Server::new_async().await;
+    let item = |n: u32, embed: &serde_json::Value| {
+        json!({
+            "post": {
+                "uri": format!("at://did:plc:artist/app.bsky.feed.post/{}", n),
+                "cid": format!("cid{}", n),
+                "author": { "did": "did:plc:artist", "handle": "artist.test" },
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "createdAt": "2024-01-01T00:00:00Z",
+                    "embed": embed
+                },
+                "indexedAt": "2024-01-01T00:00:00Z"
+            }
+        })
+    };
+    appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body(json!({ "feed": [item(1, &with_media), item(2, &quote)] }).to_string())
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let posts = client
+        .get_user_posts_with_options("artist.test", 10, 0, None, None)
+        .await
+        .unwrap();
+    let uris: Vec<_> = posts.iter().map(|p| p.uri.as_str()).collect();
+    assert_eq!(uris, vec!["at://did:plc:artist/app.bsky.feed.post/1"]);
+}
+
+#[tokio::test]
+async fn test_quoted_post_from_embed_view() {
+    let view_record = json!({
+        "$type": "app.bsky.embed.record#viewRecord",
+        "uri": "at://did:plc:quoted/app.bsky.feed.post/1",
+        "cid": "bafyquoted",
+        "author": { "did": "did:plc:quoted", "handle": "quoted.test" },
+        "value": {
+            "$type": "app.bsky.feed.post",
+            "text": "original",
+            "createdAt": "2024-01-01T00:00:00Z"
+        },
+        "labels": [{
+            "src": "did:plc:labeler",
+            "uri": "at://did:plc:quoted/app.bsky.feed.post/1",
+            "val": "sexual",
+            "cts": "2024-01-01T00:00:00Z"
+        }],
+        "indexedAt": "2024-01-01T00:00:00Z",
+        "embeds": []
+    });
+    let post_with = |embed: serde_json::Value| -> Post {
+        serde_json::from_value(json!({
+            "uri": "at://did:plc:quoter/app.bsky.feed.post/2",
+            "cid": "bafyquoter",
+            "author": { "did": "did:plc:quoter", "handle": "quoter.test" },
+            "record": { "$type": "app.bsky.feed.post", "createdAt": "2024-01-02T00:00:00Z" },
+            "embed": embed,
+            "indexedAt": "2024-01-02T00:00:00Z"
+        }))
+        .unwrap()
+    };
+
+    let quote = post_with(json!({
+        "$type": "app.bsky.embed.record#view",
+        "record": view_record.clone()
+    }));
+    let quoted = quote.quoted_post().unwrap();
+    assert_eq!(quoted.uri, "at://did:plc:quoted/app.bsky.feed.post/1");
+    assert_eq!(quoted.author.handle, "quoted.test");
+    assert_eq!(quoted.record.text.as_deref(), Some("original"));
+    assert!(quoted.has_nsfw_labels());
+
+    let with_media = post_with(json!({
+        "$type": "app.bsky.embed.recordWithMedia#view",
+        "record": { "record": view_record },
+        "media": { "$type": "app.bsky.embed.images#view", "images": [] }
+    }));
+    assert_eq!(with_media.quoted_post().unwrap().cid, "bafyquoted");
+
+    let missing = post_with(json!({
+        "$type": "app.bsky.embed.record#view",
+        "record": {
+            "$type": "app.bsky.embed.record#viewNotFound",
+            "uri": "at://did:plc:quoted/app.bsky.feed.post/1",
+            "notFound": true
+        }
+    }));
+    assert!(missing.quoted_post().is_none());
+
+    let images = post_with(json!({ "$type": "app.bsky.embed.images#view", "images": [] }));
+    assert!(images.quoted_post().is_none());
+}
+
+#[test]
+fn test_unknown_and_malformed_embeds() {
+    let post_with = |embed: serde_json::Value| -> Post {
+        serde_json::from_value(json!({
+            "uri": "at://did:plc:artist/app.bsky.feed.post/1",
+            "cid": "bafypost",
+            "author": { "did": "did:plc:artist", "handle": "artist.test" },
+            "record": {
+                "$type": "app.bsky.feed.post",
+                "text": "hello",
+                "createdAt": "2024-01-01T00:00:00Z",
+                "langs": ["en"],
+                "embed": embed.clone()
+            },
+            "embed": embed,
+            "indexedAt": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap()
+    };
+
+    // Embed types added to the lexicons later still parse
+    let unknown = post_with(json!({ "$type": "app.bsky.embed.hologram", "hologram": {} }));
+    assert!(matches!(unknown.record.embed, Some(Embed::Unknown)));
+    assert!(!unknown.record.embed.unwrap().has_media());
+    assert_eq!(unknown.record.langs, vec!["en"]);
+
+    // A malformed embed is dropped without failing the post
+    let malformed = post_with(json!({ "$type": "app.bsky.embed.images", "images": "oops" }));
+    assert!(malformed.record.embed.is_none());
+    assert_eq!(malformed.record.text.as_deref(), Some("hello"));
+
+    // Quotes of records that aren't posts, such as feeds, aren't quoted posts
+    let feed_quote = post_with(json!({
+        "$type": "app.bsky.embed.record#view",
+        "record": {
+            "$type": "app.bsky.feed.defs#generatorView",
+            "uri": "at://did:plc:artist/app.bsky.feed.generator/art"
+        }
+    }));
+    assert!(feed_quote.quoted_post().is_none());
+}
+
+#[test]
+fn test_malformed_reply_is_dropped() {
+    let post_with = |reply: serde_json::Value| -> Post {
+        serde_json::from_value(json!({
+            "uri": "at://did:plc:artist/app.bsky.feed.post/2",
+            "cid": "bafypost",
+            "author": { "did": "did:plc:artist", "handle": "artist.test" },
+            "record": {
+                "$type": "app.bsky.feed.post",
+                "text": "a reply",
+                "createdAt": "2024-01-01T00:00:00Z",
+                "reply": reply
+            },
+            "indexedAt": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap()
+    };
+
+    let reply = post_with(json!({
+        "root": { "uri": "at://did:plc:artist/app.bsky.feed.post/1", "cid": "bafyroot" },
+        "parent": { "uri": "at://did:plc:artist/app.bsky.feed.post/1", "cid": "bafyroot" }
+    }));
+    let reply_ref = reply.record.reply.unwrap();
+    assert_eq!(
+        reply_ref.parent.uri,
+        "at://did:plc:artist/app.bsky.feed.post/1"
+    );
+
+    // A reply missing its parent doesn't fail the post it's on
+    let malformed = post_with(json!({
+        "root": { "uri": "at://did:plc:artist/app.bsky.feed.post/1", "cid": "bafyroot" }
+    }));
+    assert!(malformed.record.reply.is_none());
+    assert_eq!(malformed.record.text.as_deref(), Some("a reply"));
+}
+
+#[tokio::test]
+async fn test_feed_pages_resolve_feed_url() {
+    use bluesky_archiver::paginator::PaginateOptions;
+    use futures::TryStreamExt;
+
+    let mut appview = mockito::Server::new_async().await;
+    let resolve_mock = appview
+        .mock("GET", "/xrpc/com.atproto.identity.resolveHandle")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "handle".into(),
+            "curator.test".into(),
+        ))
+        .with_status(200)
+        .with_body(json!({ "did": "did:plc:curator" }).to_string())
+        .create_async()
+        .await;
+    let feed_item = |n: u32| {
+        json!({
+            "post": {
+                "uri": format!("at://did:plc:artist/app.bsky.feed.post/{}", n),
+                "cid": format!("cid{}", n),
+                "author": { "did": "did:plc:artist", "handle": "artist.test" },
+                "record": { "$type": "app.bsky.feed.post", "createdAt": "2024-01-01T00:00:00Z" },
+                "indexedAt": "2024-01-01T00:00:00Z"
+            }
+        })
+    };
+    let feed_query = |extra: Vec<mockito::Matcher>| {
+        let mut matchers = vec![mockito::Matcher::UrlEncoded(
+            "feed".into(),
+            "at://did:plc:curator/app.bsky.feed.generator/art".into(),
+        )];
+        matchers.extend(extra);
+        mockito::Matcher::AllOf(matchers)
+    };
+    // Created first, as the first page's matcher also accepts a cursor
+    let second_page = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getFeed")
+        .match_query(feed_query(vec![mockito::Matcher::UrlEncoded(
+            "cursor".into(),
+            "page2".into(),
+        )]))
+        .with_status(200)
+        .with_body(json!({ "feed": [feed_item(3)] }).to_string())
+        .create_async()
+        .await;
+
+    let first_page = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getFeed")
+        .match_query(feed_query(vec![]))
+        .with_status(200)
+        .with_body(json!({ "feed": [feed_item(1), feed_item(2)], "cursor": "page2" }).to_string())
+        .create_async()
+        .await;
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let feed = client
+        .resolve_feed_uri("https://bsky.app/profile/curator.test/feed/art")
+        .await
+        .unwrap();
+    assert_eq!(
+        feed.to_string(),
+        "at://did:plc:curator/app.bsky.feed.generator/art"
+    );
+
+    // Resuming from a saved cursor skips the first page
+    let options = PaginateOptions {
+        start_cursor: Some("page2".to_string()),
+        ..Default::default()
+    };
+    let pages: Vec<_> = client
+        .feed_pages(&feed, options)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(
+        pages[0].items[0].uri,
+        "at://did:plc:artist/app.bsky.feed.post/3"
+    );
+    assert!(pages[0].cursor.is_none());
+
+    let options = PaginateOptions {
+        limit: 2,
+        ..Default::default()
+    };
+    let pages: Vec<_> = client
+        .feed_pages(&feed, options)
+        .try_collect()
+        .await
+        .unwrap();
+    let posts: Vec<_> = pages.iter().flat_map(|p| &p.items).collect();
+    assert_eq!(posts.len(), 2);
+
+    resolve_mock.assert_async().await;
+    first_page.assert_async().await;
+    second_page.assert_async().await;
+
+    // Lists aren't feeds
+    let err = client
+        .resolve_feed_uri("at://did:plc:curator/app.bsky.graph.list/art")
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Expected a app.bsky.feed.generator URI"));
+}
+
+#[tokio::test]
+async fn test_list_feed_and_members() {
+    use bluesky_archiver::paginator::PaginateOptions;
+    use futures::TryStreamExt;
+
+    let mut appview = mockito::Server::new_async().await;
+    let list_uri = "at://did:plc:curator/app.bsky.graph.list/artists";
+    let member = |handle: &str| {
+        json!({
+            "uri": format!("at://did:plc:curator/app.bsky.graph.listitem/{}", handle),
+            "subject": { "did": format!("did:plc:{}", handle), "handle": format!("{}.test", handle) }
+        })
+    };
+    let list_query = |cursor: Option<&str>| {
+        let mut matchers = vec![mockito::Matcher::UrlEncoded("list".into(), list_uri.into())];
+        if let Some(cursor) = cursor {
+            matchers.push(mockito::Matcher::UrlEncoded("cursor".into(), cursor.into()));
+        }
+        mockito::Matcher::AllOf(matchers)
+    };
+    let second_page = appview
+        .mock("GET", "/xrpc/app.bsky.graph.getList")
+        .match_query(list_query(Some("more")))
+        .with_status(200)
+        .with_body(json!({ "items": [member("carol")] }).to_string())
+        .create_async()
+        .await;
+    let first_page = appview
+        .mock("GET", "/xrpc/app.bsky.graph.getList")
+        .match_query(list_query(None))
+        .with_status(200)
+        .with_body(
+            json!({ "items": [member("alice"), member("bob")], "cursor": "more" }).to_string(),
+        )
+        .create_async()
+        .await;
+    let list_feed = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getListFeed")
+        .match_query(list_query(None))
+        .with_status(200)
+        .with_body(
+            json!({
+                "feed": [{
+                    "post": {
+                        "uri": "at://did:plc:alice/app.bsky.feed.post/1",
+                        "cid": "bafypost",
+                        "author": { "did": "did:plc:alice", "handle": "alice.test" },
+                        "record": { "$type": "app.bsky.feed.post", "createdAt": "2024-01-01T00:00:00Z" },
+                        "indexedAt": "2024-01-01T00:00:00Z"
+                    }
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let list = client
+        .resolve_list_uri("https://bsky.app/profile/did:plc:curator/lists/artists")
+        .await
+        .unwrap();
+    assert_eq!(list.to_string(), list_uri);
+
+    let members = client.list_members(&list, 0).await.unwrap();
+    let handles: Vec<_> = members.iter().map(|m| m.handle.as_str()).collect();
+    assert_eq!(handles, vec!["alice.test", "bob.test", "carol.test"]);
+    first_page.assert_async().await;
+    second_page.assert_async().await;
+
+    let pages: Vec<_> = client
+        .list_feed_pages(&list, PaginateOptions::default())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(pages[0].items[0].author.handle, "alice.test");
+    list_feed.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_search_pages() {
+    use bluesky_archiver::paginator::PaginateOptions;
+    use bluesky_archiver::search::SearchQuery;
+    use futures::TryStreamExt;
+
+    let mut appview = mockito::Server::new_async().await;
+    let search_mock = appview
+        .mock("GET", "/xrpc/app.bsky.feed.searchPosts")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("q".into(), "#artfest".into()),
+            mockito::Matcher::UrlEncoded("tag".into(), "artfest".into()),
+            mockito::Matcher::UrlEncoded("sort".into(), "latest".into()),
+            mockito::Matcher::UrlEncoded("cursor".into(), "25".into()),
+        ]))
+        .with_status(200)
+        .with_body(
+            json!({
+                "posts": [{
+                    "uri": "at://did:plc:artist/app.bsky.feed.post/1",
+                    "cid": "bafypost",
+                    "author": { "did": "did:plc:artist", "handle": "artist.test" },
+                    "record": { "$type": "app.bsky.feed.post", "createdAt": "2024-06-01T00:00:00Z" },
+                    "indexedAt": "2024-06-01T00:00:00Z"
+                }],
+                "cursor": "50",
+                "hitsTotal": 51
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let query = SearchQuery {
+        sort: Some("latest".to_string()),
+        tags: vec!["artfest".to_string()],
+        ..SearchQuery::new("#artfest")
+    };
+    let options = PaginateOptions {
+        limit: 1,
+        start_cursor: Some("25".to_string()),
+        ..Default::default()
+    };
+    let pages: Vec<_> = client
+        .search_pages(&query, options)
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].items[0].cid, "bafypost");
+    search_mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_follows() {
+    let mut appview = mockito::Server::new_async().await;
+    let account = |name: &str| json!({ "did": format!("did:plc:{}", name), "handle": format!("{}.test", name) });
+    let follows_query = |cursor: Option<&str>| {
+        let mut matchers = vec![mockito::Matcher::UrlEncoded(
+            "actor".into(),
+            "me.test".into(),
+        )];
+        if let Some(cursor) = cursor {
+            matchers.push(mockito::Matcher::UrlEncoded("cursor".into(), cursor.into()));
+        }
+        mockito::Matcher::AllOf(matchers)
+    };
+    let second_page = appview
+        .mock("GET", "/xrpc/app.bsky.graph.getFollows")
+        .match_query(follows_query(Some("more")))
+        .with_status(200)
+        .with_body(json!({ "subject": account("me"), "follows": [account("carol")] }).to_string())
+        .create_async()
+        .await;
+    let first_page = appview
+        .mock("GET", "/xrpc/app.bsky.graph.getFollows")
+        .match_query(follows_query(None))
+        .with_status(200)
+        .with_body(
+            json!({
+                "subject": account("me"),
+                "follows": [account("alice"), account("bob")],
+                "cursor": "more"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let follows = client.follows("me.test", 0).await.unwrap();
+    let dids: Vec<_> = follows.iter().map(|a| a.did.as_str()).collect();
+    assert_eq!(dids, vec!["did:plc:alice", "did:plc:bob", "did:plc:carol"]);
+
+    first_page.assert_async().await;
+    second_page.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_timeline_requires_auth() {
+    use bluesky_archiver::paginator::PaginateOptions;
+    use futures::TryStreamExt;
+
+    let result: Result<Vec<_>, _> = Client::public()
+        .timeline_pages(PaginateOptions::default())
+        .try_collect()
+        .await;
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("Not authenticated"));
+}
+
+#[tokio::test]
+async fn test_reposts_pages() {
+    use bluesky_archiver::paginator::PaginateOptions;
+    use futures::TryStreamExt;
+
+    let mut appview = mockito::Server::new_async().await;
+    let post = |rkey: &str| {
+        let mut post = json!({
+            "uri": format!("at://did:plc:artist/app.bsky.feed.post/{}", rkey),
+            "cid": format!("bafy{}", rkey),
+            "author": { "did": "did:plc:artist", "handle": "artist.test" },
+            "record": { "$type": "app.bsky.feed.post", "text": rkey, "createdAt": "2024-01-01T00:00:00Z" },
+            "indexedAt": "2024-01-01T00:00:00Z"
+        });
+        if rkey != "text" {
+            post["record"]["embed"] = json!({
+                "$type": "app.bsky.embed.images",
+                "images": [{
+                    "alt": "",
+                    "image": {
+                        "$type": "blob",
+                        "ref": { "$link": format!("bafyblob{}", rkey) },
+                        "mimeType": "image/jpeg",
+                        "size": 5
+                    }
+                }]
+            });
+        }
+        post
+    };
+    let reposted = |rkey: &str, at: &str| {
+        json!({
+            "post": post(rkey),
+            "reason": {
+                "$type": "app.bsky.feed.defs#reasonRepost",
+                "by": { "did": "did:plc:curator", "handle": "curator.test" },
+                "indexedAt": at
+            }
+        })
+    };
+    let feed = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("actor".into(), "curator.test".into()),
+            mockito::Matcher::UrlEncoded("filter".into(), "posts_no_replies".into()),
+        ]))
+        .with_status(200)
+        .with_body(
+            json!({
+                "feed": [
+                    { "post": post("pinned"), "reason": { "$type": "app.bsky.feed.defs#reasonPin" } },
+                    reposted("first", "2024-03-02T00:00:00Z"),
+                    { "post": post("own") },
+                    reposted("text", "2024-03-01T12:00:00Z"),
+                    reposted("second", "2024-03-01T00:00:00Z")
+                ]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let pages: Vec<_> = client
+        .reposts_pages("curator.test", PaginateOptions::default())
+        .try_collect()
+        .await
+        .unwrap();
+    let reposts: Vec<_> = pages.into_iter().flat_map(|page| page.items).collect();
+
+    let summary: Vec<_> = reposts
+        .iter()
+        .map(|r| {
+            (
+                r.post.cid.as_str(),
+                r.by.did.as_str(),
+                r.reposted_at.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("bafyfirst", "did:plc:curator", "2024-03-02T00:00:00Z"),
+            ("bafysecond", "did:plc:curator", "2024-03-01T00:00:00Z"),
+        ]
+    );
+    feed.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_post_thread_not_found() {
+    let mut appview = mockito::Server::new_async().await;
+    let not_found = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getPostThread")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "uri".into(),
+            "at://did:plc:artist/app.bsky.feed.post/gone".into(),
+        ))
+        .with_status(200)
+        .with_body(
+            json!({
+                "thread": {
+                    "$type": "app.bsky.feed.defs#notFoundPost",
+                    "uri": "at://did:plc:artist/app.bsky.feed.post/gone",
+                    "notFound": true
+                }
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let post = client
+        .resolve_post_uri("https://bsky.app/profile/did:plc:artist/post/gone")
+        .await
+        .unwrap();
+    let err = client.get_post_thread(&post, 6, 80).await.unwrap_err();
+    assert!(err.to_string().contains("not found"));
+    not_found.assert_async().await;
+
+    // Feeds aren't posts
+    assert!(client
+        .resolve_post_uri("https://bsky.app/profile/did:plc:artist/feed/art")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_get_posts_in_batches() {
+    let mut appview = mockito::Server::new_async().await;
+    let resolve_mock = appview
+        .mock("GET", "/xrpc/com.atproto.identity.resolveHandle")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "handle".into(),
+            "artist.test".into(),
+        ))
+        .with_status(200)
+        .with_body(json!({ "did": "did:plc:artist" }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+    let post = |rkey: &str| {
+        json!({
+            "uri": format!("at://did:plc:artist/app.bsky.feed.post/{}", rkey),
+            "cid": format!("cid{}", rkey),
+            "author": { "did": "did:plc:artist", "handle": "artist.test" },
+            "record": { "$type": "app.bsky.feed.post", "createdAt": "2024-01-01T00:00:00Z" },
+            "indexedAt": "2024-01-01T00:00:00Z"
+        })
+    };
+    // The last two of 27 posts go in a second request
+    let second_batch = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getPosts")
+        .match_query(mockito::Matcher::Regex(r"feed\.post%2F26$".into()))
+        .with_status(200)
+        .with_body(json!({ "posts": [post("25"), post("26")] }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+    let first_batch = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getPosts")
+        .match_query(mockito::Matcher::Regex(r"feed\.post%2F0&".into()))
+        .with_status(200)
+        // One of the 25 was deleted
+        .with_body(
+            json!({ "posts": (0..24).map(|n| post(&n.to_string())).collect::<Vec<_>>() })
+                .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    let urls: Vec<String> = (0..27)
+        .map(|n| match n % 2 {
+            0 => format!("https://bsky.app/profile/artist.test/post/{}", n),
+            _ => format!("at://artist.test/app.bsky.feed.post/{}", n),
+        })
+        .collect();
+    let uris = client.resolve_post_uris(&urls).await.unwrap();
+    assert!(uris.iter().all(|uri| uri.authority == "did:plc:artist"));
+
+    let posts = client.get_posts(&uris).await.unwrap();
+    assert_eq!(posts.len(), 26);
+    assert_eq!(posts[25].cid, "cid26");
+
+    resolve_mock.assert_async().await;
+    first_batch.assert_async().await;
+    second_batch.assert_async().await;
+}
@@ -1,4 +1,4 @@
-use bluesky_archiver::database::{ArchivedImage, ArchivedPost, Database};
+use bluesky_archiver::database::{ArchivedImage, ArchivedPost, ArchivedVideo, Database};
 use chrono::Utc;
 use tempfile::tempdir;
 
@@ -165,3 +165,97 @@ fn test_special_characters_in_text() {
     db.save_post(&post).unwrap();
     assert!(db.is_post_archived(&post.uri).unwrap());
 }
+
+#[test]
+fn test_save_and_get_video() {
+    let (db, _temp_dir) = create_test_db();
+
+    assert!(!db.is_video_archived("video_cid").unwrap());
+    assert!(db.get_video("video_cid").unwrap().is_none());
+
+    db.save_post(&ArchivedPost {
+        uri: "at://test.post/video".to_string(),
+        cid: "video_post_cid".to_string(),
+        author_did: "did:plc:testuser".to_string(),
+        author_handle: "testuser.bsky.social".to_string(),
+        post_text: None,
+        image_count: 0,
+        archived_at: Utc::now(),
+        post_created_at: "2024-01-01T00:00:00Z".to_string(),
+        has_content_warning: false,
+    })
+    .unwrap();
+
+    let video = ArchivedVideo {
+        id: 0,
+        post_uri: "at://test.post/video".to_string(),
+        blob_cid: "video_cid".to_string(),
+        filename: "clip.mp4".to_string(),
+        mime_type: "video/mp4".to_string(),
+        size: 4096,
+        duration: Some(12.5),
+        aspect_width: Some(16),
+        aspect_height: Some(9),
+        alt_text: Some("A clip".to_string()),
+        source: "blob".to_string(),
+        downloaded_at: Utc::now(),
+    };
+    db.save_video(&video).unwrap();
+
+    assert!(db.is_video_archived("video_cid").unwrap());
+    let saved = db.get_video("video_cid").unwrap().unwrap();
+    assert_eq!(saved.filename, "clip.mp4");
+    assert_eq!(saved.duration, Some(12.5));
+    assert_eq!(saved.aspect_width, Some(16));
+    assert_eq!(saved.aspect_height, Some(9));
+    assert_eq!(saved.alt_text.as_deref(), Some("A clip"));
+
+    // Videos don't count towards the image total
+    let (_, image_count) = db.get_stats().unwrap();
+    assert_eq!(image_count, 0);
+}
+
+#[test]
+fn test_account_cursors() {
+    let (db, _dir) = create_test_db();
+
+    assert_eq!(db.get_account_cursor("did:plc:artist").unwrap(), None);
+
+    db.save_account_cursor("did:plc:artist", "artist.test", "page2")
+        .unwrap();
+    db.save_account_cursor("did:plc:artist", "artist.test", "page3")
+        .unwrap();
+    db.save_account_cursor("did:plc:other", "other.test", "page9")
+        .unwrap();
+    assert_eq!(
+        db.get_account_cursor("did:plc:artist").unwrap().as_deref(),
+        Some("page3")
+    );
+
+    db.clear_account_cursor("did:plc:artist").unwrap();
+    assert_eq!(db.get_account_cursor("did:plc:artist").unwrap(), None);
+    assert_eq!(
+        db.get_account_cursor("did:plc:other").unwrap().as_deref(),
+        Some("page9")
+    );
+}
+
+#[test]
+fn test_concurrent_connections() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let first = Database::new(&db_path).unwrap();
+    let second = Database::new(&db_path).unwrap();
+
+    // Cursors written through one connection are seen by the other
+    second
+        .save_account_cursor("did:plc:artist", "artist.test", "page2")
+        .unwrap();
+    assert_eq!(
+        first
+            .get_account_cursor("did:plc:artist")
+            .unwrap()
+            .as_deref(),
+        Some("page2")
+    );
+}
@@ -0,0 +1,71 @@
+use bluesky_archiver::hls::{best_variant, is_master, parse_master, parse_media, resolve, Variant};
+
+const MASTER: &str = "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-STREAM-INF:BANDWIDTH=1000000,RESOLUTION=640x360,CODECS=\"avc1.64001e,mp4a.40.2\"
+360p/video.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=2800000,RESOLUTION=1280x720,CODECS=\"avc1.64001f,mp4a.40.2\"
+720p/video.m3u8
+";
+
+const MEDIA: &str = "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-TARGETDURATION:6
+#EXTINF:6.000,
+video0.ts
+#EXTINF:6.000,
+video1.ts
+#EXTINF:2.5,
+video2.ts
+#EXT-X-ENDLIST
+";
+
+#[test]
+fn test_parse_master_playlist() {
+    assert!(is_master(MASTER));
+    assert!(!is_master(MEDIA));
+
+    assert_eq!(
+        parse_master(MASTER),
+        vec![
+            Variant {
+                bandwidth: 1_000_000,
+                uri: "360p/video.m3u8".to_string()
+            },
+            Variant {
+                bandwidth: 2_800_000,
+                uri: "720p/video.m3u8".to_string()
+            },
+        ]
+    );
+    assert_eq!(best_variant(MASTER).unwrap().uri, "720p/video.m3u8");
+}
+
+#[test]
+fn test_parse_media_playlist() {
+    let media = parse_media(MEDIA).unwrap();
+    assert_eq!(media.segments, vec!["video0.ts", "video1.ts", "video2.ts"]);
+    assert_eq!(media.init, None);
+    assert!((media.duration - 14.5).abs() < f64::EPSILON);
+    assert_eq!(media.mime_type(), "video/mp2t");
+
+    let fmp4 = "#EXTM3U\n#EXT-X-MAP:URI=\"init.mp4\"\n#EXTINF:4.0,\nseg0.m4s\n";
+    let media = parse_media(fmp4).unwrap();
+    assert_eq!(media.init.as_deref(), Some("init.mp4"));
+    assert_eq!(media.mime_type(), "video/mp4");
+
+    assert!(parse_media("#EXTM3U\n#EXT-X-ENDLIST\n").is_err());
+}
+
+#[test]
+fn test_resolve_relative_uris() {
+    let base = "https://video.test/watch/did%3Aplc%3Aa/bafy/playlist.m3u8";
+    assert_eq!(
+        resolve(base, "720p/video.m3u8").unwrap(),
+        "https://video.test/watch/did%3Aplc%3Aa/bafy/720p/video.m3u8"
+    );
+    assert_eq!(
+        resolve(base, "https://cdn.test/seg.ts").unwrap(),
+        "https://cdn.test/seg.ts"
+    );
+}
@@ -0,0 +1,77 @@
+use bluesky_archiver::identity::{did_web_url, DidDocument, IdentityResolver};
+use serde_json::json;
+
+fn did_document(did: &str, pds: &str) -> serde_json::Value {
+    json!({
+        "@context": ["https://www.w3.org/ns/did/v1"],
+        "id": did,
+        "alsoKnownAs": ["at://someone.example"],
+        "verificationMethod": [],
+        "service": [{
+            "id": "#atproto_pds",
+            "type": "AtprotoPersonalDataServer",
+            "serviceEndpoint": pds
+        }]
+    })
+}
+
+#[test]
+fn test_did_web_url() {
+    assert_eq!(
+        did_web_url("did:web:example.com").unwrap(),
+        "https://example.com/.well-known/did.json"
+    );
+    assert_eq!(
+        did_web_url("did:web:example.com:user:alice").unwrap(),
+        "https://example.com/user/alice/did.json"
+    );
+    assert_eq!(
+        did_web_url("did:web:localhost%3A8443").unwrap(),
+        "https://localhost:8443/.well-known/did.json"
+    );
+    assert!(did_web_url("did:plc:abc").is_err());
+    assert!(did_web_url("did:web:").is_err());
+}
+
+#[test]
+fn test_pds_endpoint_from_document() {
+    let document: DidDocument =
+        serde_json::from_value(did_document("did:plc:abc", "https://pds.example/")).unwrap();
+    assert_eq!(document.pds_endpoint(), Some("https://pds.example"));
+
+    let no_pds: DidDocument = serde_json::from_value(json!({
+        "id": "did:plc:abc",
+        "service": [{
+            "id": "#bsky_fg",
+            "type": "BskyFeedGenerator",
+            "serviceEndpoint": "https://feed.example"
+        }]
+    }))
+    .unwrap();
+    assert_eq!(no_pds.pds_endpoint(), None);
+}
+
+#[tokio::test]
+async fn test_resolve_pds_via_plc_directory() {
+    let mut plc = mockito::Server::new_async().await;
+    let mock = plc
+        .mock("GET", "/did:plc:abc")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(did_document("did:plc:abc", "https://pds.example").to_string())
+        .create_async()
+        .await;
+
+    let resolver = IdentityResolver::new(reqwest::Client::new(), &plc.url());
+    let pds = resolver.resolve_pds("did:plc:abc").await.unwrap();
+    assert_eq!(pds, "https://pds.example");
+    mock.assert_async().await;
+
+    // A document for a different DID must be rejected
+    plc.mock("GET", "/did:plc:other")
+        .with_status(200)
+        .with_body(did_document("did:plc:abc", "https://pds.example").to_string())
+        .create_async()
+        .await;
+    assert!(resolver.resolve_pds("did:plc:other").await.is_err());
+}
@@ -0,0 +1,301 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bluesky_archiver::bluesky::{Client, Endpoints};
+use bluesky_archiver::oauth::{pkce_challenge, DpopKey, OAuthSession};
+use bluesky_archiver::session::{Credentials, SessionStore, StoredSession};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::Signature;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tempfile::tempdir;
+
+fn decode_segment(segment: &str) -> Value {
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(segment).unwrap()).unwrap()
+}
+
+fn oauth_session(pds: &str, token_endpoint: &str, key: &DpopKey) -> OAuthSession {
+    OAuthSession {
+        did: "did:plc:tester".to_string(),
+        pds: pds.to_string(),
+        issuer: pds.to_string(),
+        token_endpoint: token_endpoint.to_string(),
+        client_id: "http://localhost".to_string(),
+        access_token: "oauth-access".to_string(),
+        refresh_token: "oauth-refresh".to_string(),
+        expires_at: None,
+        dpop_key: key.encode(),
+        auth_server_nonce: None,
+        resource_nonce: None,
+    }
+}
+
+#[test]
+fn test_pkce_challenge() {
+    // base64url(sha256(verifier)) without padding
+    assert_eq!(
+        pkce_challenge("dBjftJeZ4CVP-mJ0kiF6oTLodE2bgb3M7lSNuC9DERY"),
+        "oiIYCil9NQ2G6k46w98m24B_ZOkF6-FkDDyKjXcpW5w"
+    );
+}
+
+#[test]
+fn test_dpop_proof() {
+    let key = DpopKey::generate();
+    let restored = DpopKey::from_encoded(&key.encode()).unwrap();
+    assert_eq!(key.public_jwk(), restored.public_jwk());
+
+    let proof = key
+        .proof(
+            "GET",
+            "https://pds.example/xrpc/app.bsky.feed.getActorLikes?actor=me&limit=100",
+            Some("server-nonce"),
+            Some("access-token"),
+        )
+        .unwrap();
+
+    let parts: Vec<&str> = proof.split('.').collect();
+    assert_eq!(parts.len(), 3);
+
+    let header = decode_segment(parts[0]);
+    assert_eq!(header["typ"], "dpop+jwt");
+    assert_eq!(header["alg"], "ES256");
+    assert_eq!(header["jwk"], key.public_jwk());
+
+    let claims = decode_segment(parts[1]);
+    assert_eq!(claims["htm"], "GET");
+    assert_eq!(
+        claims["htu"],
+        "https://pds.example/xrpc/app.bsky.feed.getActorLikes"
+    );
+    assert_eq!(claims["nonce"], "server-nonce");
+    assert_eq!(
+        claims["ath"],
+        URL_SAFE_NO_PAD.encode(Sha256::digest(b"access-token"))
+    );
+    assert!(claims["jti"].as_str().is_some());
+
+    let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap()).unwrap();
+    key.verifying_key()
+        .verify(format!("{}.{}", parts[0], parts[1]).as_bytes(), &signature)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_oauth_session_renews_nonce_and_refreshes() {
+    let mut server = mockito::Server::new_async().await;
+    let dir = tempdir().unwrap();
+    let key = DpopKey::generate();
+    let token_endpoint = format!("{}/oauth/token", server.url());
+
+    let store = SessionStore::new(dir.path().join("session.json"));
+    store
+        .save(&StoredSession {
+            identifier: "tester".to_string(),
+            service: "https://entryway.example".to_string(),
+            credentials: Credentials::OAuth(oauth_session(&server.url(), &token_endpoint, &key)),
+        })
+        .unwrap();
+
+    // The PDS first wants a nonce, then reports the token as expired
+    let nonce_mock = server
+        .mock("GET", "/xrpc/com.atproto.server.getSession")
+        .match_header("authorization", "DPoP oauth-access")
+        .match_header("dpop", mockito::Matcher::Any)
+        .with_status(401)
+        .with_header("www-authenticate", r#"DPoP error="use_dpop_nonce""#)
+        .with_header("dpop-nonce", "nonce-1")
+        .expect(1)
+        .create_async()
+        .await;
+    let expired_mock = server
+        .mock("GET", "/xrpc/com.atproto.server.getSession")
+        .match_header("authorization", "DPoP oauth-access")
+        .with_status(401)
+        .with_header("www-authenticate", r#"DPoP error="invalid_token""#)
+        .create_async()
+        .await;
+    let refresh_mock = server
+        .mock("POST", "/oauth/token")
+        .match_header("dpop", mockito::Matcher::Any)
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("grant_type".into(), "refresh_token".into()),
+            mockito::Matcher::UrlEncoded("refresh_token".into(), "oauth-refresh".into()),
+        ]))
+        .with_status(200)
+        .with_body(
+            json!({
+                "access_token": "oauth-access-2",
+                "token_type": "DPoP",
+                "refresh_token": "oauth-refresh-2",
+                "expires_in": 3600,
+                "sub": "did:plc:tester"
+            })
+            .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+    let ok_mock = server
+        .mock("GET", "/xrpc/com.atproto.server.getSession")
+        .match_header("authorization", "DPoP oauth-access-2")
+        .with_status(200)
+        .with_body(json!({ "did": "did:plc:tester", "handle": "tester" }).to_string())
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        pds: "https://entryway.example".to_string(),
+        ..Default::default()
+    });
+    client.set_session_store(SessionStore::new(dir.path().join("session.json")));
+    client
+        .resume_or_login_oauth("tester", |_| panic!("should reuse the saved session"))
+        .await
+        .unwrap();
+
+    nonce_mock.assert_async().await;
+    expired_mock.assert_async().await;
+    refresh_mock.assert_async().await;
+    ok_mock.assert_async().await;
+
+    match store.load().unwrap().unwrap().credentials {
+        Credentials::OAuth(session) => {
+            assert_eq!(session.access_token, "oauth-access-2");
+            assert_eq!(session.refresh_token, "oauth-refresh-2");
+            assert_eq!(session.dpop_key, key.encode());
+            assert!(session.expires_at.is_some());
+        }
+        other => panic!("Expected OAuth credentials, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_oauth_authorization_code_flow() {
+    let mut server = mockito::Server::new_async().await;
+    let url = server.url();
+
+    server
+        .mock("GET", "/xrpc/com.atproto.identity.resolveHandle")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "handle".into(),
+            "tester.test".into(),
+        ))
+        .with_status(200)
+        .with_body(json!({ "did": "did:plc:tester" }).to_string())
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/did:plc:tester")
+        .with_status(200)
+        .with_body(
+            json!({
+                "id": "did:plc:tester",
+                "service": [{
+                    "id": "#atproto_pds",
+                    "type": "AtprotoPersonalDataServer",
+                    "serviceEndpoint": url
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/.well-known/oauth-protected-resource")
+        .with_status(200)
+        .with_body(json!({ "authorization_servers": [url] }).to_string())
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/.well-known/oauth-authorization-server")
+        .with_status(200)
+        .with_body(
+            json!({
+                "issuer": url,
+                "authorization_endpoint": format!("{}/oauth/authorize", url),
+                "token_endpoint": format!("{}/oauth/token", url),
+                "pushed_authorization_request_endpoint": format!("{}/oauth/par", url)
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    // Echo the state back through the request_uri so the fake browser can use it
+    let par_mock = server
+        .mock("POST", "/oauth/par")
+        .match_header("dpop", mockito::Matcher::Any)
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("code_challenge_method".into(), "S256".into()),
+            mockito::Matcher::UrlEncoded("login_hint".into(), "tester.test".into()),
+        ]))
+        .with_status(201)
+        .with_body_from_request(|request| {
+            let body = String::from_utf8(request.body().unwrap().clone()).unwrap();
+            let form = reqwest::Url::parse(&format!("http://form/?{}", body)).unwrap();
+            let state = form
+                .query_pairs()
+                .find(|(k, _)| k == "state")
+                .map(|(_, v)| v.to_string())
+                .unwrap();
+            json!({ "request_uri": format!("urn:test:{}", state), "expires_in": 60 })
+                .to_string()
+                .into_bytes()
+        })
+        .create_async()
+        .await;
+    let token_mock = server
+        .mock("POST", "/oauth/token")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("grant_type".into(), "authorization_code".into()),
+            mockito::Matcher::UrlEncoded("code".into(), "auth-code".into()),
+        ]))
+        .with_status(200)
+        .with_body(
+            json!({
+                "access_token": "oauth-access",
+                "token_type": "DPoP",
+                "refresh_token": "oauth-refresh",
+                "expires_in": 3600,
+                "sub": "did:plc:tester"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        appview: url.clone(),
+        plc_directory: url.clone(),
+        ..Default::default()
+    });
+
+    let issuer = url.clone();
+    client
+        .login_oauth("tester.test", move |authorize_url| {
+            let authorize_url = reqwest::Url::parse(authorize_url).unwrap();
+            let param = |url: &reqwest::Url, name: &str| {
+                url.query_pairs()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.to_string())
+                    .unwrap()
+            };
+            let client_id = reqwest::Url::parse(&param(&authorize_url, "client_id")).unwrap();
+            let redirect_uri = param(&client_id, "redirect_uri");
+            let state = param(&authorize_url, "request_uri")
+                .trim_start_matches("urn:test:")
+                .to_string();
+
+            let callback = reqwest::Url::parse_with_params(
+                &redirect_uri,
+                &[("code", "auth-code"), ("state", &state), ("iss", &issuer)],
+            )
+            .unwrap();
+            tokio::spawn(async move { reqwest::get(callback).await.unwrap() });
+        })
+        .await
+        .unwrap();
+
+    par_mock.assert_async().await;
+    token_mock.assert_async().await;
+}
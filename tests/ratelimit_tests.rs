@@ -0,0 +1,171 @@
+use bluesky_archiver::bluesky::{Client, Endpoints};
+use bluesky_archiver::ratelimit::{RateLimiter, RateLimits};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+#[tokio::test]
+async fn test_budget_follows_remaining_header() {
+    let limiter = RateLimiter::new("test", 100, Duration::from_secs(60));
+    assert_eq!(limiter.available(), 100);
+
+    limiter.update(&headers(&[
+        ("ratelimit-limit", "100".into()),
+        ("ratelimit-remaining", "3".into()),
+    ]));
+    assert_eq!(limiter.available(), 3);
+
+    limiter.acquire().await;
+    assert_eq!(limiter.available(), 2);
+
+    // A higher remaining count never adds tokens we've already spent
+    limiter.update(&headers(&[("ratelimit-remaining", "50".into())]));
+    assert_eq!(limiter.available(), 2);
+
+    // Responses without rate limit headers leave the budget alone
+    limiter.update(&HeaderMap::new());
+    assert_eq!(limiter.available(), 2);
+}
+
+#[tokio::test]
+async fn test_exhausted_budget_waits_for_reset() {
+    // Refilling one token takes a minute, so only the reset can unblock us
+    let limiter = RateLimiter::new("test", 5, Duration::from_secs(300));
+    let reset = chrono::Utc::now().timestamp() + 2;
+    limiter.update(&headers(&[
+        ("ratelimit-limit", "5".into()),
+        ("ratelimit-remaining", "0".into()),
+        ("ratelimit-reset", reset.to_string()),
+    ]));
+    assert_eq!(limiter.available(), 0);
+
+    let started = Instant::now();
+    limiter.acquire().await;
+    let waited = started.elapsed();
+    assert!(waited >= Duration::from_millis(900), "waited {:?}", waited);
+    assert!(waited <= Duration::from_secs(3), "waited {:?}", waited);
+
+    // The whole budget is back after the reset
+    assert_eq!(limiter.available(), 4);
+}
+
+#[tokio::test]
+async fn test_policy_window_sets_refill_rate() {
+    let limiter = RateLimiter::new("test", 1000, Duration::from_secs(300));
+    limiter.update(&headers(&[
+        ("ratelimit-limit", "20".into()),
+        ("ratelimit-remaining", "0".into()),
+        ("ratelimit-policy", "20;w=1".into()),
+    ]));
+
+    // 20 per second means a token every 50ms
+    let started = Instant::now();
+    limiter.acquire().await;
+    limiter.acquire().await;
+    let waited = started.elapsed();
+    assert!(waited >= Duration::from_millis(80), "waited {:?}", waited);
+    assert!(waited <= Duration::from_millis(500), "waited {:?}", waited);
+}
+
+#[tokio::test]
+async fn test_appview_and_blob_budgets_are_separate() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_header("ratelimit-limit", "3000")
+        .with_header("ratelimit-remaining", "10")
+        .with_body(json!({ "feed": [] }).to_string())
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_header("ratelimit-limit", "500")
+        .with_header("ratelimit-remaining", "200")
+        .with_body("bytes")
+        .create_async()
+        .await;
+
+    let client = Client::with_endpoints(Endpoints {
+        appview: server.url(),
+        blob_host: server.url(),
+        ..Default::default()
+    });
+
+    client
+        .get_user_posts_with_options("artist.test", 10, 0, None, None)
+        .await
+        .unwrap();
+    client
+        .download_image(&client.get_image_url("did:plc:author", "bafyblob"))
+        .await
+        .unwrap();
+
+    let limits: &RateLimits = client.rate_limits();
+    assert_eq!(limits.appview.available(), 10);
+    assert_eq!(limits.blob_host(&server.host_with_port()).available(), 200);
+
+    // Clones share the same budgets
+    let clone = client.clone();
+    clone.rate_limits().appview.acquire().await;
+    assert_eq!(client.rate_limits().appview.available(), 9);
+}
+
+#[tokio::test]
+async fn test_blob_budgets_are_per_host() {
+    let mut small_pds = mockito::Server::new_async().await;
+    let big_pds = mockito::Server::new_async().await;
+    small_pds
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_header("ratelimit-limit", "10")
+        .with_header("ratelimit-remaining", "2")
+        .with_body("bytes")
+        .create_async()
+        .await;
+    // Linked media and HLS segments aren't XRPC calls and have no budget
+    small_pds
+        .mock("GET", "/media/cat.gif")
+        .with_status(200)
+        .with_header("ratelimit-limit", "10")
+        .with_header("ratelimit-remaining", "0")
+        .with_body("gif")
+        .create_async()
+        .await;
+
+    let client = Client::new();
+    let url = client.get_blob_url(&small_pds.url(), "did:plc:author", "bafyblob");
+    client.download_image(&url).await.unwrap();
+    client
+        .download(
+            &format!("{}/media/cat.gif", small_pds.url()),
+            "linked media",
+        )
+        .await
+        .unwrap();
+
+    let limits = client.rate_limits();
+    assert_eq!(limits.blob_host(&small_pds.host_with_port()).available(), 2);
+    // Another PDS keeps its own budget, and the AppView's is untouched
+    assert_eq!(
+        limits.blob_host(&big_pds.host_with_port()).available(),
+        3000
+    );
+    assert_eq!(limits.appview.available(), 3000);
+    assert!(limits
+        .for_url(&format!("{}/media/cat.gif", small_pds.url()))
+        .is_none());
+}
@@ -0,0 +1,254 @@
+use bluesky_archiver::bluesky::{Client, Endpoints};
+use bluesky_archiver::retry::{ratelimit_reset, retry_after, RetryPolicy};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::json;
+use std::time::Duration;
+
+fn fast_policy() -> RetryPolicy {
+    RetryPolicy {
+        max_retries: 3,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+    }
+}
+
+#[test]
+fn test_backoff_grows_with_jitter() {
+    let policy = RetryPolicy {
+        max_retries: 5,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+    };
+
+    for _ in 0..20 {
+        let first = policy.backoff(1);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+        let third = policy.backoff(3);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+
+        // Capped at max_delay
+        let tenth = policy.backoff(10);
+        assert!(tenth >= Duration::from_millis(500) && tenth <= Duration::from_millis(1000));
+    }
+}
+
+#[test]
+fn test_server_delay_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert("retry-after", HeaderValue::from_static("7"));
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+    // Dates in the past mean "retry now"
+    headers.insert(
+        "retry-after",
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+    );
+    assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+    let reset = chrono::Utc::now().timestamp() + 30;
+    headers.insert(
+        "ratelimit-reset",
+        HeaderValue::from_str(&reset.to_string()).unwrap(),
+    );
+    let delay = ratelimit_reset(&headers).unwrap();
+    assert!(delay > Duration::from_secs(28) && delay <= Duration::from_secs(30));
+
+    assert_eq!(retry_after(&HeaderMap::new()), None);
+    assert_eq!(ratelimit_reset(&HeaderMap::new()), None);
+}
+
+#[tokio::test]
+async fn test_listing_retries_server_errors() {
+    let mut appview = mockito::Server::new_async().await;
+
+    let unavailable = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Any)
+        .with_status(503)
+        .with_header("retry-after", "0")
+        .expect(2)
+        .create_async()
+        .await;
+    let feed = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body(json!({ "feed": [] }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    client.set_retry_policy(fast_policy());
+
+    let posts = client
+        .get_user_posts_with_options("artist.test", 10, 0, None, None)
+        .await
+        .unwrap();
+    assert!(posts.is_empty());
+
+    unavailable.assert_async().await;
+    feed.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_retries() {
+    let mut appview = mockito::Server::new_async().await;
+
+    let rate_limited = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Any)
+        .with_status(429)
+        .expect(4)
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    client.set_retry_policy(fast_policy());
+
+    let err = client
+        .get_user_posts_with_options("artist.test", 10, 0, None, None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Rate limited"));
+
+    rate_limited.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_blob_download_retries() {
+    let mut server = mockito::Server::new_async().await;
+
+    let bad_gateway = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(502)
+        .expect(1)
+        .create_async()
+        .await;
+    let blob = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body("bytes")
+        .expect(1)
+        .create_async()
+        .await;
+
+    // A PDS the client wasn't configured with takes the unauthenticated path
+    let mut client = Client::new();
+    client.set_retry_policy(fast_policy());
+
+    let url = client.get_blob_url(&server.url(), "did:plc:author", "bafyblob");
+    let bytes = client.download_image(&url).await.unwrap();
+    assert_eq!(bytes, b"bytes");
+
+    bad_gateway.assert_async().await;
+    blob.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_connection_errors_are_retried() {
+    // Nothing listens on this port, so every attempt fails to connect
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/xrpc/com.atproto.sync.getBlob?did=did:plc:author&cid=bafyblob",
+        listener.local_addr().unwrap()
+    );
+    drop(listener);
+
+    let mut client = Client::new();
+    client.set_retry_policy(fast_policy());
+
+    let started = std::time::Instant::now();
+    assert!(client.download_image(&url).await.is_err());
+    // Three backoffs of at least 5ms, 10ms and 20ms
+    assert!(started.elapsed() >= Duration::from_millis(35));
+}
+
+#[tokio::test]
+async fn test_bodies_cut_off_are_retried() {
+    use std::io::{Error, ErrorKind};
+
+    let mut server = mockito::Server::new_async().await;
+
+    // The connection drops after the headers and part of the body
+    let cut_off = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_chunked_body(|w| {
+            w.write_all(b"by")?;
+            Err(Error::new(ErrorKind::ConnectionReset, "connection reset"))
+        })
+        .expect(1)
+        .create_async()
+        .await;
+    let blob = server
+        .mock("GET", "/xrpc/com.atproto.sync.getBlob")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body("bytes")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = Client::new();
+    client.set_retry_policy(fast_policy());
+
+    let url = client.get_blob_url(&server.url(), "did:plc:author", "bafyblob");
+    let bytes = client.download_image(&url).await.unwrap();
+    assert_eq!(bytes, b"bytes");
+
+    cut_off.assert_async().await;
+    blob.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_pages_cut_off_are_retried() {
+    use std::io::{Error, ErrorKind};
+
+    let mut appview = mockito::Server::new_async().await;
+
+    let cut_off = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_chunked_body(|w| {
+            w.write_all(br#"{"feed": ["#)?;
+            Err(Error::new(ErrorKind::ConnectionReset, "connection reset"))
+        })
+        .expect(1)
+        .create_async()
+        .await;
+    let feed = appview
+        .mock("GET", "/xrpc/app.bsky.feed.getAuthorFeed")
+        .match_query(mockito::Matcher::Any)
+        .with_status(200)
+        .with_body(json!({ "feed": [] }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        appview: appview.url(),
+        ..Default::default()
+    });
+    client.set_retry_policy(fast_policy());
+
+    let posts = client
+        .get_user_posts_with_options("artist.test", 10, 0, None, None)
+        .await
+        .unwrap();
+    assert!(posts.is_empty());
+
+    cut_off.assert_async().await;
+    feed.assert_async().await;
+}
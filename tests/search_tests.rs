@@ -0,0 +1,48 @@
+use bluesky_archiver::search::SearchQuery;
+
+#[test]
+fn test_search_params() {
+    let query = SearchQuery {
+        author: Some("artist.test".to_string()),
+        lang: Some("en".to_string()),
+        since: Some("2024-06-01".to_string()),
+        sort: Some("latest".to_string()),
+        tags: vec!["#artfest".to_string(), "oc".to_string()],
+        ..SearchQuery::new("#artfest")
+    };
+
+    assert_eq!(
+        query.params(),
+        vec![
+            ("q", "#artfest".to_string()),
+            ("author", "artist.test".to_string()),
+            ("lang", "en".to_string()),
+            ("since", "2024-06-01".to_string()),
+            ("sort", "latest".to_string()),
+            ("tag", "artfest".to_string()),
+            ("tag", "oc".to_string()),
+        ]
+    );
+
+    // Unset filters aren't sent
+    assert_eq!(
+        SearchQuery::new("cats").params(),
+        vec![("q", "cats".to_string())]
+    );
+}
+
+#[test]
+fn test_search_file_key() {
+    let key = SearchQuery::new("#artfest").file_key();
+    assert_eq!(key.len(), 16);
+    assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+
+    // The same search resumes from the same cursor, a different one doesn't
+    assert_eq!(key, SearchQuery::new("#artfest").file_key());
+    let latest = SearchQuery {
+        sort: Some("latest".to_string()),
+        ..SearchQuery::new("#artfest")
+    };
+    assert_ne!(key, latest.file_key());
+    assert_ne!(key, SearchQuery::new("#artfest2").file_key());
+}
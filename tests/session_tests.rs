@@ -0,0 +1,179 @@
+use bluesky_archiver::bluesky::{Client, Endpoints};
+use bluesky_archiver::session::{Credentials, Session, SessionStore, StoredSession};
+use serde_json::json;
+use tempfile::tempdir;
+
+fn stored_session(identifier: &str, service: &str) -> StoredSession {
+    StoredSession {
+        identifier: identifier.to_string(),
+        service: service.to_string(),
+        credentials: Credentials::AppPassword(Session {
+            did: "did:plc:tester".to_string(),
+            access_jwt: "saved-access".to_string(),
+            refresh_jwt: "saved-refresh".to_string(),
+        }),
+    }
+}
+
+#[test]
+fn test_session_store_roundtrip() {
+    let dir = tempdir().unwrap();
+    let store = SessionStore::new(dir.path().join("session.json"));
+
+    assert!(store.load().unwrap().is_none());
+
+    store
+        .save(&stored_session("tester", "https://pds.example"))
+        .unwrap();
+    let loaded = store.load().unwrap().unwrap();
+    assert_eq!(loaded.identifier, "tester");
+    assert_eq!(loaded.credentials.access_token(), "saved-access");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(store.path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn test_saved_session_is_reused() {
+    let mut server = mockito::Server::new_async().await;
+    let dir = tempdir().unwrap();
+    let store = SessionStore::new(dir.path().join("session.json"));
+    store
+        .save(&stored_session("tester", &server.url()))
+        .unwrap();
+
+    let login_mock = server
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .expect(0)
+        .create_async()
+        .await;
+    let get_session = server
+        .mock("GET", "/xrpc/com.atproto.server.getSession")
+        .match_header("authorization", "Bearer saved-access")
+        .with_status(200)
+        .with_body(json!({ "did": "did:plc:tester", "handle": "tester" }).to_string())
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        pds: server.url(),
+        ..Default::default()
+    });
+    client.set_session_store(store);
+    client
+        .resume_or_login("tester", "password", None)
+        .await
+        .unwrap();
+
+    login_mock.assert_async().await;
+    get_session.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_server_errors_keep_saved_session() {
+    use bluesky_archiver::retry::RetryPolicy;
+    use std::time::Duration;
+
+    let mut server = mockito::Server::new_async().await;
+    let dir = tempdir().unwrap();
+    let store = SessionStore::new(dir.path().join("session.json"));
+    store
+        .save(&stored_session("tester", &server.url()))
+        .unwrap();
+
+    let unavailable = server
+        .mock("GET", "/xrpc/com.atproto.server.getSession")
+        .with_status(503)
+        .expect(2)
+        .create_async()
+        .await;
+    let login_mock = server
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        pds: server.url(),
+        ..Default::default()
+    });
+    client.set_retry_policy(RetryPolicy {
+        max_retries: 1,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(10),
+    });
+    client.set_session_store(SessionStore::new(dir.path().join("session.json")));
+
+    // An outage is reported instead of spending a login on it
+    assert!(client
+        .resume_or_login("tester", "password", None)
+        .await
+        .is_err());
+    unavailable.assert_async().await;
+    login_mock.assert_async().await;
+    assert_eq!(
+        store.load().unwrap().unwrap().credentials.access_token(),
+        "saved-access"
+    );
+}
+
+#[tokio::test]
+async fn test_failed_refresh_falls_back_to_login() {
+    let mut server = mockito::Server::new_async().await;
+    let dir = tempdir().unwrap();
+    let store = SessionStore::new(dir.path().join("session.json"));
+    store
+        .save(&stored_session("tester", &server.url()))
+        .unwrap();
+
+    server
+        .mock("GET", "/xrpc/com.atproto.server.getSession")
+        .with_status(400)
+        .with_body(json!({ "error": "ExpiredToken" }).to_string())
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/xrpc/com.atproto.server.refreshSession")
+        .with_status(400)
+        .with_body(json!({ "error": "ExpiredToken" }).to_string())
+        .create_async()
+        .await;
+    let login_mock = server
+        .mock("POST", "/xrpc/com.atproto.server.createSession")
+        .with_status(200)
+        .with_body(
+            json!({ "did": "did:plc:tester", "accessJwt": "fresh-access", "refreshJwt": "fresh-refresh" })
+                .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = Client::with_endpoints(Endpoints {
+        pds: server.url(),
+        ..Default::default()
+    });
+    client.set_session_store(SessionStore::new(dir.path().join("session.json")));
+    client
+        .resume_or_login("tester", "password", None)
+        .await
+        .unwrap();
+    login_mock.assert_async().await;
+
+    // The fresh tokens replace the stale ones on disk
+    let saved = store.load().unwrap().unwrap();
+    match saved.credentials {
+        Credentials::AppPassword(session) => {
+            assert_eq!(session.access_jwt, "fresh-access");
+            assert_eq!(session.refresh_jwt, "fresh-refresh");
+        }
+        other => panic!("Expected app password credentials, got {:?}", other),
+    }
+}